@@ -8,3 +8,6 @@ pub const IPV6_MIN_MTU: u32 = 1280;
 
 pub const PRIO_MIN: i8 = -20;
 pub const PRIO_MAX: i8 = 20;
+
+/// CPUAffinity=中允许的CPU编号上限(不含)，与CPU_SETSIZE一致
+pub const CPU_SET_SIZE: u32 = 1024;
@@ -1,110 +1,193 @@
-use std::sync::{Arc, Mutex};
+use hashbrown::HashMap;
 
 use crate::manager::UnitManager;
-use crate::{
-    error::runtime_error::{RuntimeError, RuntimeErrorType},
-    unit::Unit,
-};
+
+/// 依赖关系类型，Before=按照反向的After边记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepType {
+    // 排序依赖，边的起点在终点启动完成之后才能启动
+    After,
+    Requires,
+    Wants,
+    BindsTo,
+    PartOf,
+}
+
+impl DepType {
+    /// 排序依赖
+    pub const ORDERING: &'static [DepType] = &[DepType::After];
+}
 
 #[allow(dead_code)]
 pub struct DepGraphNode {
     value: usize,
-    edges: Vec<usize>,
-    incoming_edges: Vec<usize>,
+    edges: Vec<(usize, DepType)>,
 }
 
+/// ## Unit依赖图
+///
+/// 节点为Unit的id，边记录依赖关系类型，可按依赖类型查找环
 #[allow(dead_code)]
 pub struct DepGraph {
     nodes: Vec<DepGraphNode>,
-    value: Vec<usize>,
+    index: HashMap<usize, usize>,
 }
 
-// 提供拓扑排序方法，在启动服务时确定先后顺序
 #[allow(dead_code)]
 impl DepGraph {
-    fn new() -> Self {
+    pub fn new() -> Self {
         return DepGraph {
             nodes: Vec::new(),
-            value: Vec::new(),
+            index: HashMap::new(),
         };
     }
 
     pub fn add_node(&mut self, value: usize) -> usize {
-        let index = self.nodes.len();
         //如果nodes中已经有了这个value则无需重复添加，直接返回nodes中的value对应的index
-        if let Some(idx) = self.value.iter().position(|x| *x == value) {
-            return idx;
+        if let Some(idx) = self.index.get(&value) {
+            return *idx;
         }
-        //如果value在nodes中不存在，则添加value
+        let index = self.nodes.len();
         self.nodes.push(DepGraphNode {
-            value: value,
+            value,
             edges: Vec::new(),
-            incoming_edges: Vec::new(),
         });
-        self.value.push(value);
+        self.index.insert(value, index);
         return index;
     }
-    pub fn add_edge(&mut self, from: usize, to: usize) {
-        self.nodes[from].edges.push(to);
-        self.nodes[to].incoming_edges.push(from);
+
+    pub fn add_edge(&mut self, from: usize, to: usize, dep: DepType) {
+        if !self.nodes[from].edges.contains(&(to, dep)) {
+            self.nodes[from].edges.push((to, dep));
+        }
     }
-    pub fn topological_sort(&mut self) -> Result<Vec<usize>, RuntimeError> {
-        let mut result = Vec::new();
-        let mut visited = Vec::new();
-        let mut stack = Vec::new();
-        for (i, node) in self.nodes.iter().enumerate() {
-            if node.incoming_edges.len() == 0 {
-                stack.push(i);
-            }
+
+    /// ## 构建指定Unit之间的依赖图
+    ///
+    /// 只记录两端都在units中的边，不存在的Unit被忽略
+    pub fn from_units(units: &[usize]) -> DepGraph {
+        let mut graph = DepGraph::new();
+        for id in units {
+            graph.add_node(*id);
         }
-        while stack.len() > 0 {
-            let index = stack.pop().unwrap();
-            if visited.contains(&index) {
-                continue;
+        for id in units {
+            let unit = match UnitManager::get_unit_with_id(id) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            let part = unit.lock().unwrap().unit_base().unit_part().clone();
+            let from = graph.add_node(*id);
+            let deps = [
+                (part.after(), DepType::After),
+                (part.requires(), DepType::Requires),
+                (part.wants(), DepType::Wants),
+                (part.binds_to(), DepType::BindsTo),
+                (part.part_of(), DepType::PartOf),
+            ];
+            for (targets, dep) in deps {
+                for target in targets {
+                    if let Some(to) = graph.index.get(target).copied() {
+                        graph.add_edge(from, to, dep);
+                    }
+                }
             }
-            visited.push(index);
-            result.push(self.nodes[index].value);
-            let len = self.nodes[index].edges.len();
-            for i in 0..len {
-                let edge = self.nodes[index].edges[i];
-                self.nodes[edge].incoming_edges.retain(|&x| x != index);
-                if self.nodes[edge].incoming_edges.len() == 0 {
-                    stack.push(edge);
+            // Before=记录为对方的After=
+            for target in part.before() {
+                if let Some(to) = graph.index.get(target).copied() {
+                    graph.add_edge(to, from, DepType::After);
                 }
             }
         }
-        if result.len() != self.nodes.len() {
-            return Err(RuntimeError::new(RuntimeErrorType::CircularDependency));
-        }
-        result.reverse();
-        return Ok(result);
+        graph
     }
 
-    fn add_edges(&mut self, unit: usize, after: &[usize]) {
-        //因为service的依赖关系规模不会很大，故先使用递归实现
-        //TODO:改递归
-        for target in after {
-            let s = self.add_node(unit);
-            let t = self.add_node(*target);
-            self.add_edge(s, t);
-
-            let arc_unit = UnitManager::get_unit_with_id(target).unwrap();
-            let unit = arc_unit.lock().unwrap();
-            let after = unit.unit_base().unit_part().after();
+    /// ## 构建所有已加载Unit的依赖图
+    pub fn from_loaded_units() -> DepGraph {
+        let mut units = UnitManager::get_all_units();
+        units.sort();
+        Self::from_units(&units)
+    }
 
-            self.add_edges(*target, after);
+    /// ## 只沿指定类型的边查找环
+    ///
+    /// ### return 找到环时返回环上的Unit id，首尾为同一个Unit，如[a, b, a]
+    pub fn find_cycle(&self, types: &[DepType]) -> Option<Vec<usize>> {
+        // 0:未访问 1:在当前路径上 2:已完成
+        let mut color = vec![0u8; self.nodes.len()];
+        for start in 0..self.nodes.len() {
+            if color[start] != 0 {
+                continue;
+            }
+            // 以显式栈进行深度优先搜索，栈中记录节点及下一条待访问的边
+            let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+            color[start] = 1;
+            while let Some((node, edge)) = stack.last().copied() {
+                let next = self.nodes[node].edges[edge..]
+                    .iter()
+                    .position(|(_, dep)| types.contains(dep))
+                    .map(|i| edge + i);
+                let next = match next {
+                    Some(next) => next,
+                    None => {
+                        color[node] = 2;
+                        stack.pop();
+                        continue;
+                    }
+                };
+                stack.last_mut().unwrap().1 = next + 1;
+                let (to, _) = self.nodes[node].edges[next];
+                match color[to] {
+                    0 => {
+                        color[to] = 1;
+                        stack.push((to, 0));
+                    }
+                    1 => {
+                        let pos = stack.iter().position(|(n, _)| *n == to).unwrap();
+                        let mut cycle: Vec<usize> = stack[pos..]
+                            .iter()
+                            .map(|(n, _)| self.nodes[*n].value)
+                            .collect();
+                        cycle.push(self.nodes[to].value);
+                        return Some(cycle);
+                    }
+                    _ => {}
+                }
+            }
         }
+        None
+    }
+
+    /// ## 移除环上的一条指定类型的边，用于在报告后继续查找其他环
+    pub fn remove_edge(&mut self, from: usize, to: usize, types: &[DepType]) {
+        let (from, to) = match (self.index.get(&from), self.index.get(&to)) {
+            (Some(from), Some(to)) => (*from, *to),
+            _ => return,
+        };
+        self.nodes[from]
+            .edges
+            .retain(|(t, dep)| *t != to || !types.contains(dep));
     }
 
-    pub fn construct_graph(unit: &Arc<Mutex<dyn Unit>>) -> DepGraph {
-        let mut graph: DepGraph = DepGraph::new();
+    /// ## 将环格式化为a.service -> b.service -> a.service的形式
+    pub fn format_cycle(cycle: &[usize]) -> String {
+        cycle
+            .iter()
+            .map(|id| match UnitManager::get_unit_with_id(id) {
+                Some(unit) => unit.lock().unwrap().unit_base().unit_name(),
+                None => id.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(" -> ")
+    }
 
-        let unit = unit.lock().unwrap();
-        let uid = unit.unit_id();
-        graph.add_node(uid);
-        let after = (&unit).unit_base().unit_part().after();
-        //递归添加边来构建图
-        graph.add_edges(uid, after);
-        return graph;
+    /// ## 检查所有已加载Unit间的排序环
+    ///
+    /// 排序环不会阻止Unit的加载，启动时由作业事务处理，此处仅报告所有环的完整路径
+    pub fn check_loaded_units() {
+        let mut graph = Self::from_loaded_units();
+        while let Some(cycle) = graph.find_cycle(DepType::ORDERING) {
+            eprintln!("Found ordering cycle: {}", Self::format_cycle(&cycle));
+            graph.remove_edge(cycle[0], cycle[1], DepType::ORDERING);
+        }
     }
 }
@@ -3,8 +3,8 @@ pub mod service_executor;
 
 use crate::{
     error::runtime_error::{RuntimeError, RuntimeErrorType},
-    manager::UnitManager,
-    unit::UnitState,
+    manager::{process_manager::ProcessExit, UnitManager},
+    unit::{UnitState, UnitSubState},
 };
 
 #[allow(dead_code)]
@@ -30,113 +30,78 @@ impl ExitStatus {
             _ => return Self::Abnormal,
         }
     }
+
+    /// 序列化时使用的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Abnormal => "abnormal",
+            Self::Abort => "abort",
+            Self::Watchdog => "watchdog",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Success,
+            Self::Failure,
+            Self::Abnormal,
+            Self::Abort,
+            Self::Watchdog,
+        ]
+        .into_iter()
+        .find(|status| status.name() == name)
+    }
+
+    /// ## 从进程的退出状态获得退出状态
+    ///
+    /// 被信号终止视为异常退出
+    pub fn from_process_exit(exit: ProcessExit) -> Self {
+        match exit {
+            ProcessExit::Exited(code) => Self::from_exit_code(code),
+            ProcessExit::Killed(_) | ProcessExit::Dumped(_) => Self::Abnormal,
+        }
+    }
 }
 
 //Unit的全局执行器
 pub struct Executor;
 
 impl Executor {
-    /// ## 全局执行器入口，将会进行启动检测以及循环依赖检测
+    /// ## 全局执行器入口，启动Unit自身
+    ///
+    /// 依赖项由作业系统按照排序依赖分别启动，此处不再处理
     pub fn exec(unit_id: usize) -> Result<(), RuntimeError> {
-        // TODO: 添加超时检测，这个工作应该在线程执行
+        let mutex = match UnitManager::get_unit_with_id(&unit_id) {
+            Some(s) => s,
+            None => {
+                return Err(RuntimeError::new(RuntimeErrorType::FileNotFound));
+            }
+        };
+        let mut unit = mutex.lock().unwrap();
 
-        {
-            // 设置Unit状态为正在启动
-            // TODO: 目前单线程工作这样设置是无意义的
-            UnitManager::get_unit_with_id(&unit_id)
-                .unwrap()
-                .lock()
-                .unwrap()
-                .unit_base_mut()
-                .set_state(UnitState::Activating);
-        }
-        match Self::exec_(unit_id) {
+        // 设置Unit状态为正在启动
+        unit.unit_base_mut().set_state(UnitState::Activating);
+        match unit.run() {
             Ok(_) => {
-                UnitManager::get_unit_with_id(&unit_id)
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .unit_base_mut()
-                    .set_state(UnitState::Active);
+                // Service的启动命令异步执行，由其在ExecStartPost完成后自行设置状态
+                let starting = matches!(
+                    unit.unit_base().sub_state(),
+                    UnitSubState::StartPre | UnitSubState::StartPost
+                );
+                if *unit.unit_base().state() == UnitState::Activating && !starting {
+                    unit.unit_base_mut().set_state(UnitState::Active);
+                }
                 Ok(())
             }
             Err(e) => {
-                let mutex = UnitManager::get_unit_with_id(&unit_id).unwrap();
-                let mut unit = mutex.lock().unwrap();
-
-                // 启动失败时启动onfailure项目
-                for id in unit.unit_base().unit_part().on_failure() {
-                    // TODO: 待日志库开发后，这里的错误处理应该是打印日志
-                    let _ = Executor::exec(*id);
-                }
-
                 unit.unit_base_mut().set_state(UnitState::Failed);
                 unit.after_exit(ExitStatus::Failure);
                 return Err(e);
             }
         }
     }
-    pub fn exec_(unit_id: usize) -> Result<(), RuntimeError> {
-        // TODO： 目前的启动逻辑还是串行启动，后续需更改为并行启动某些项
-
-        let unit = match UnitManager::get_unit_with_id(&unit_id) {
-            Some(s) => s,
-            None => {
-                return Err(RuntimeError::new(RuntimeErrorType::FileNotFound));
-            }
-        };
-
-        let mut unit = unit.lock().unwrap();
-
-        //TODO: 优化此处，解析时也用到了拓扑排序，尝试使用那次拓扑排序的结果
-        // 此处不需要再次拓扑排序，在parse时已经确定不会出现循环依赖，现在仅需按照启动流程启动即可
-        // let mut graph = DepGraph::construct_graph(&unit);
-        // let sort_ret = graph.topological_sort()?;
-
-        // 优先启动After
-        for u in unit.unit_base().unit_part().after() {
-            if UnitManager::is_running_unit(&u) {
-                continue;
-            }
-            let mutex = UnitManager::get_unit_with_id(&u).unwrap();
-            let mut after = mutex.lock().unwrap();
-            after.run()?;
-        }
-
-        // 启动Requires
-        for u in unit.unit_base().unit_part().requires() {
-            if UnitManager::is_running_unit(&u) {
-                continue;
-            }
-            let mutex = UnitManager::get_unit_with_id(&u).unwrap();
-            let mut after = mutex.lock().unwrap();
-            after.run()?;
-        }
-
-        // 启动binds
-        for u in unit.unit_base().unit_part().binds_to() {
-            if UnitManager::is_running_unit(&u) {
-                continue;
-            }
-            let mutex = UnitManager::get_unit_with_id(&u).unwrap();
-            let mut after = mutex.lock().unwrap();
-            after.run()?;
-        }
-
-        // 启动Wants
-        for u in unit.unit_base().unit_part().wants() {
-            if UnitManager::is_running_unit(&u) {
-                continue;
-            }
-            let mutex = UnitManager::get_unit_with_id(&u).unwrap();
-            let mut after = mutex.lock().unwrap();
-            let _ = after.run();
-        }
-
-        // 启动自身
-        unit.run()?;
-        return Ok(());
-    }
 
     pub fn restart(id: usize) -> Result<(), RuntimeError> {
         if let Some(unit) = UnitManager::get_unit_with_id(&id) {
@@ -1,15 +1,23 @@
-use std::{
-    process::{Command, Stdio},
-    time::Duration,
+use std::time::Duration;
+
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
 };
 
 use crate::{
     error::runtime_error::{RuntimeError, RuntimeErrorType},
-    manager::{timer_manager::TimerManager, UnitManager},
+    manager::{
+        job_manager::{JobManager, JobMode, JobType},
+        process_manager::{ProcessExit, ProcessManager, ProcessOwner},
+        timer_manager::TimerManager,
+        UnitManager,
+    },
     parse::Segment,
+    task::{cmdtask::CmdTask, kill_context::KillMode},
     unit::{
-        service::{ServiceType, ServiceUnit},
-        Unit, UnitState,
+        service::{RestartOption, ServiceType, ServiceUnit},
+        Unit, UnitState, UnitSubState,
     },
 };
 
@@ -52,35 +60,27 @@ impl ServiceExecutor {
 
         //TODO:设置uid与gid
 
-        //处理ExecStartsPre,准备在服务启动前执行的命令
-        Self::exec_start_pre(service)?;
+        //标准输入为终端时占用该终端，同一终端只能被一个服务控制
+        let id = service.unit_id();
+        let stdio = service.service_part().exec_context().stdio();
+        if !UnitManager::acquire_tty(id, stdio) {
+            eprintln!(
+                "{}: Service startup failed: {} is used by another unit",
+                exec_start.path,
+                stdio.tty_path()
+            );
+            return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
+        }
+        stdio.reset_tty();
 
-        //创建服务进程
-        //服务配置环境变量，配置工作目录
-        let proc = Command::new(&exec_start.path)
-            .args(&exec_start.cmd)
-            .current_dir(service.service_part().working_directory())
-            .envs(Vec::from(service.service_part().environment()))
-            .stderr(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .stdin(Stdio::inherit())
-            .spawn();
+        //开始记录服务的进程，停止时按照KillMode终止整个进程树
+        ProcessManager::watch_unit(id, *service.service_part().kill_context());
 
-        match proc {
-            Ok(p) => {
-                // TODO: 打日志
-                //修改service状态
-                service.unit_base_mut().set_state(UnitState::Active);
-                //启动成功后将Child加入全局管理的进程表
-                UnitManager::push_running(service.unit_id(), p);
-                //执行启动后命令
-                Self::exec_start_pos(service)?;
-            }
-            Err(err) => {
-                eprintln!("{}: Service startup failed: {}", exec_start.path, err);
-                return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
-            }
-        }
+        //处理ExecStartsPre,准备在服务启动前执行的命令，全部成功退出后再创建服务进程
+        service.control_mut().result = None;
+        service.unit_base_mut().set_state(UnitState::Activating);
+        let cmds = service.service_part().exec_start_pre().clone();
+        Self::enter_phase(service, UnitSubState::StartPre, cmds);
         Ok(())
     }
 
@@ -108,64 +108,242 @@ impl ServiceExecutor {
         Ok(())
     }
 
-    fn exec_start_pos(service: &ServiceUnit) -> Result<(), RuntimeError> {
-        let cmds = service.service_part().exec_start_pos();
-        for cmd in cmds {
-            cmd.spawn()?;
+    /// ## 创建服务进程，随后执行ExecStartPost
+    fn exec_start(service: &mut ServiceUnit) {
+        //服务配置环境变量，配置工作目录
+        //标准输入输出、资源限制等执行上下文在子进程exec之前应用
+        let id = service.unit_id();
+        let proc = service
+            .service_part()
+            .exec_start()
+            .command()
+            .and_then(|mut command| ProcessManager::spawn(&mut command, ProcessOwner::Main(id)));
+
+        match proc {
+            Ok(p) => {
+                // TODO: 打日志
+                //启动成功后将主进程加入全局管理的进程表
+                UnitManager::push_running(id, Pid::from_raw(p.id() as i32));
+                //执行启动后命令
+                let cmds = service.service_part().exec_start_pos().clone();
+                Self::enter_phase(service, UnitSubState::StartPost, cmds);
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}: Service startup failed: {}",
+                    service.service_part().exec_start().path,
+                    err
+                );
+                Self::set_result(service, ExitStatus::Failure);
+                Self::enter_stop_post(service);
+            }
         }
-        Ok(())
     }
 
-    fn exec_start_pre(service: &ServiceUnit) -> Result<(), RuntimeError> {
-        let cmds = service.service_part().exec_start_pre();
-        for cmd in cmds {
-            cmd.no_spawn()?;
+    /// ## 进入执行控制命令的阶段
+    ///
+    /// 依次执行该阶段的命令，上一个命令的退出被分发后才执行下一个命令，全部执行完毕后进入下一阶段。
+    /// ExecStartPost沿用ExecStartPre开始时设置的超时，使TimeoutStartSec限制整个启动过程
+    fn enter_phase(service: &mut ServiceUnit, phase: UnitSubState, cmds: Vec<CmdTask>) {
+        service.unit_base_mut().set_sub_state(phase);
+        let control = service.control_mut();
+        control.abandon();
+        control.pending = cmds.into();
+        if phase != UnitSubState::StartPost {
+            Self::arm_phase_timeout(service);
         }
-        Ok(())
+        Self::run_next_control(service);
     }
 
-    //显式停止时执行的命令
-    fn exec_stop(service: &mut ServiceUnit) -> Result<(), RuntimeError> {
-        let cmds = service.service_part().exec_stop();
-        for cmd in cmds {
-            cmd.no_spawn()?;
+    /// ## 执行当前阶段的下一个控制命令
+    fn run_next_control(service: &mut ServiceUnit) {
+        loop {
+            let mut cmd = match service.control_mut().pending.pop_front() {
+                Some(cmd) => cmd,
+                None => return Self::phase_done(service, true),
+            };
+            if cmd.spawn().is_err() {
+                return Self::phase_done(service, false);
+            }
+            // 忽略错误的命令创建失败时直接执行下一个命令
+            if cmd.pid == 0 {
+                continue;
+            }
+            service.control_mut().running = Some(cmd);
+            return;
         }
-        Ok(())
     }
 
-    //停止后执行的命令
-    fn exec_stop_post(service: &mut ServiceUnit) -> Result<(), RuntimeError> {
-        let cmds = service.mut_service_part().mut_exec_stop_post();
-        for cmd in cmds {
-            cmd.no_spawn()?;
+    /// ## 为当前阶段设置超时时间，取消之前阶段的超时
+    ///
+    /// 启动及重新加载阶段使用TimeoutStartSec，停止阶段使用TimeoutStopSec，为0时不超时。
+    /// 阶段结束时通过phase_done取消
+    fn arm_phase_timeout(service: &ServiceUnit) {
+        let id = service.unit_id();
+        TimerManager::cancel_timer(id);
+        let ns = match *service.unit_base().sub_state() {
+            UnitSubState::Stop | UnitSubState::StopPost => {
+                service.service_part().timeout_stop_sec()
+            }
+            _ => service.service_part().timeout_start_sec(),
+        };
+        if ns == 0 {
+            return;
         }
-        Ok(())
+        TimerManager::push_timer(
+            Duration::from_nanos(ns),
+            move || {
+                Self::phase_timeout(id);
+                Ok(())
+            },
+            id,
+        );
     }
 
-    fn exec_reload(service: &mut ServiceUnit) -> Result<(), RuntimeError> {
-        let cmds = service.service_part().exec_reload();
-        for cmd in cmds {
-            cmd.no_spawn()?;
+    /// ## 当前阶段超时，终止正在运行的控制命令，其退出被分发后当前阶段失败
+    fn phase_timeout(id: usize) {
+        let unit = match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => unit,
+            None => return,
+        };
+        let mut unit = unit.lock().unwrap();
+        let service = match unit.as_mut_any().downcast_mut::<ServiceUnit>() {
+            Some(service) => service,
+            None => return,
+        };
+        let pid = match service.control().pid() {
+            Some(pid) => pid,
+            None => return,
+        };
+        eprintln!(
+            "{}: {:?} timed out, killing control process {}",
+            service.unit_base().unit_name(),
+            service.unit_base().sub_state(),
+            pid
+        );
+        service.control_mut().timed_out = true;
+        let _ = killpg(pid, Signal::SIGKILL);
+    }
+
+    /// ## 控制进程退出，执行下一个控制命令或进入下一阶段
+    pub fn control_exit(service: &mut ServiceUnit, pid: Pid, exit: ProcessExit) {
+        // 已被放弃的控制进程
+        if service.control().pid() != Some(pid) {
+            return;
         }
-        Ok(())
+        let control = service.control_mut();
+        let cmd = control.running.take().unwrap();
+        let timed_out = std::mem::take(&mut control.timed_out);
+
+        if !timed_out && (exit.is_success() || cmd.ignore) {
+            Self::run_next_control(service);
+            return;
+        }
+        eprintln!(
+            "{}: control process {} failed: {} {}",
+            cmd.path,
+            pid,
+            exit.code(),
+            exit.status()
+        );
+        Self::phase_done(service, false);
     }
 
-    /// ## 服务退出执行的逻辑(包括自然退出及显式退出)
-    pub fn after_exit(service: &mut ServiceUnit, exit_status: ExitStatus) {
-        //TODO: 需要考虑是否需要在此处执行退出后代码，还是只需要显式退出时才执行
-        let _ = Self::exec_stop_post(service);
+    /// ## 当前阶段的控制命令全部执行完毕或失败，进入下一阶段
+    fn phase_done(service: &mut ServiceUnit, success: bool) {
+        let sub_state = *service.unit_base().sub_state();
+        // 启动过程的超时在ExecStartPost结束时取消
+        if !(sub_state == UnitSubState::StartPre && success) {
+            TimerManager::cancel_timer(service.unit_id());
+        }
+        match sub_state {
+            UnitSubState::StartPre if success => Self::exec_start(service),
+            UnitSubState::StartPre => {
+                Self::set_result(service, ExitStatus::Failure);
+                Self::enter_stop_post(service);
+            }
+            UnitSubState::StartPost if success => {
+                service.unit_base_mut().set_sub_state(UnitSubState::Running);
+                service.unit_base_mut().set_state(UnitState::Active);
+            }
+            // ExecStartPost失败时停止已启动的服务进程
+            UnitSubState::StartPost => {
+                Self::set_result(service, ExitStatus::Failure);
+                Self::enter_stop_sigterm(service);
+            }
+            UnitSubState::Reload => {
+                if !success {
+                    eprintln!("{}: reload failed", service.unit_base().unit_name());
+                }
+                service.unit_base_mut().set_sub_state(UnitSubState::Running);
+                service.unit_base_mut().set_state(UnitState::Active);
+            }
+            UnitSubState::Stop => Self::enter_stop_sigterm(service),
+            UnitSubState::StopPost => Self::enter_dead(service),
+            _ => {}
+        }
+    }
 
-        // 停止被spawn的命令
-        let s_part = service.mut_service_part();
-        for cmd in s_part.mut_exec_start_pos() {
-            cmd.stop()
+    /// 记录本次运行的结果，只保留第一个结果
+    fn set_result(service: &mut ServiceUnit, result: ExitStatus) {
+        service.control_mut().result.get_or_insert(result);
+    }
+
+    /// ## 执行ExecStop
+    fn enter_stop(service: &mut ServiceUnit) {
+        // 服务进程在启动过程中退出时取消启动的超时
+        TimerManager::cancel_timer(service.unit_id());
+        service.unit_base_mut().set_state(UnitState::Deactivating);
+        let cmds = service.service_part().exec_stop().clone();
+        Self::enter_phase(service, UnitSubState::Stop, cmds);
+    }
+
+    /// ## 按照KillMode终止服务的进程，主进程的退出被分发后执行ExecStopPost
+    fn enter_stop_sigterm(service: &mut ServiceUnit) {
+        service.unit_base_mut().set_state(UnitState::Deactivating);
+        service.control_mut().abandon();
+
+        // 向服务的进程发送KillSignal，TimeoutStopSec后仍未退出的进程将被SIGKILL
+        let ns = service.service_part().timeout_stop_sec();
+        let id = service.unit_id();
+        ProcessManager::stop_unit(id, Duration::from_nanos(ns));
+
+        // KillMode=none时不会终止主进程，不再等待其退出
+        if service.service_part().kill_context().kill_mode == KillMode::None {
+            UnitManager::remove_running(id);
         }
-        for cmd in s_part.mut_exec_start_pre() {
-            cmd.stop()
+        if UnitManager::running_pid(id).is_some() {
+            service
+                .unit_base_mut()
+                .set_sub_state(UnitSubState::StopSigterm);
+        } else {
+            Self::enter_stop_post(service);
         }
+    }
+
+    /// ## 执行ExecStopPost
+    fn enter_stop_post(service: &mut ServiceUnit) {
+        service.unit_base_mut().set_state(UnitState::Deactivating);
+        let cmds = service.mut_service_part().exec_stop_post().clone();
+        Self::enter_phase(service, UnitSubState::StopPost, cmds);
+    }
+
+    /// ## 服务的所有阶段结束，根据本次运行的结果决定是否重启
+    fn enter_dead(service: &mut ServiceUnit) {
+        let id = service.unit_id();
+        let result = service
+            .control_mut()
+            .result
+            .take()
+            .unwrap_or(ExitStatus::Success);
+        service.control_mut().abandon();
+        service.unit_base_mut().set_sub_state(UnitSubState::Dead);
+
+        // 服务进程未创建时占用的终端在此释放
+        UnitManager::release_tty(id);
 
         // 取消未进行的定时器任务
-        TimerManager::cancel_timer(service.unit_id());
+        TimerManager::cancel_timer(id);
 
         // 关闭和此服务绑定的项目
         for bind in service.unit_base().unit_part().be_binded_by() {
@@ -173,36 +351,70 @@ impl ServiceExecutor {
         }
 
         //判断是否需要restart，需要则再次启动服务
-        if service.service_part().restart().is_restart(&exit_status) {
+        //显式停止的服务只在Restart=on-abort时重启
+        let restart = service.service_part().restart();
+        let restart = match result {
+            ExitStatus::Abort => *restart == RestartOption::OnAbort,
+            _ => restart.is_restart(&result),
+        };
+        if restart {
+            service.control_mut().n_restarts += 1;
+            service.unit_base_mut().set_state(UnitState::Activating);
+            service
+                .unit_base_mut()
+                .set_sub_state(UnitSubState::AutoRestart);
             let _ = Self::restart(service);
             return;
         }
 
         //如果该进程标记了RemainAfterExit，则将其加入特殊标记表
-        if service.service_part().remain_after_exit() {
-            UnitManager::push_flag_running(service.unit_id());
+        if service.service_part().remain_after_exit() && !matches!(result, ExitStatus::Abort) {
+            UnitManager::push_flag_running(id);
+            service.unit_base_mut().set_state(UnitState::Active);
             return;
         }
 
         //停止服务后设置Unit状态
-        service.unit_base_mut().set_state(UnitState::Inactive);
+        let state = match result {
+            ExitStatus::Failure => UnitState::Failed,
+            _ => UnitState::Inactive,
+        };
+        service.unit_base_mut().set_state(state);
+    }
+
+    /// ## 服务主进程退出后执行的逻辑(包括自然退出及显式退出)
+    ///
+    /// 自然退出时执行ExecStop，显式停止时主进程的退出被分发后执行ExecStopPost
+    pub fn after_exit(service: &mut ServiceUnit, exit_status: ExitStatus) {
+        Self::set_result(service, exit_status);
+        match *service.unit_base().sub_state() {
+            UnitSubState::StopSigterm => Self::enter_stop_post(service),
+            // 正在执行停止命令，当前阶段结束后继续
+            UnitSubState::Stop | UnitSubState::StopPost => {}
+            UnitSubState::StartPost | UnitSubState::Running | UnitSubState::Reload => {
+                Self::enter_stop(service)
+            }
+            // 服务进程未运行(如依赖启动失败)
+            _ => Self::enter_dead(service),
+        }
     }
 
     /// ## 重启Service
     pub fn restart(service: &mut ServiceUnit) -> Result<(), RuntimeError> {
         let ns = service.service_part().restart_sec();
-        let binds = service.unit_base().unit_part().be_binded_by();
-        let binds = Vec::from(binds);
+        let part = service.unit_base().unit_part();
+        let binds: Vec<usize> = part
+            .be_binded_by()
+            .iter()
+            .chain(part.consists_of())
+            .cloned()
+            .collect();
         let id = service.unit_id();
         if ns > 0 {
-            let cmds = service.service_part().exec_reload().clone();
             TimerManager::push_timer(
                 Duration::from_nanos(ns),
                 move || {
-                    for cmd in &cmds {
-                        cmd.no_spawn()?;
-                    }
-                    Executor::exec(id)?;
+                    JobManager::add_job(id, JobType::Start, JobMode::Replace)?;
                     for bind in &binds {
                         Executor::restart(*bind)?
                     }
@@ -212,7 +424,6 @@ impl ServiceExecutor {
             )
         } else {
             UnitManager::try_kill_running(id);
-            Self::exec_reload(service)?;
             eprintln!("restart");
             Self::exec(service)?;
             for bind in &binds {
@@ -222,25 +433,103 @@ impl ServiceExecutor {
         Ok(())
     }
 
+    /// ## 重新加载Service，执行ExecReload
+    pub fn reload(service: &mut ServiceUnit) -> Result<(), RuntimeError> {
+        if *service.unit_base().sub_state() != UnitSubState::Running {
+            eprintln!(
+                "{}: Service is not running",
+                service.unit_base().unit_name()
+            );
+            return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
+        }
+        service.unit_base_mut().set_state(UnitState::Reloading);
+        let cmds = service.service_part().exec_reload().clone();
+        Self::enter_phase(service, UnitSubState::Reload, cmds);
+        Ok(())
+    }
+
+    /// ## 接管daemon-reexec之前服务仍在运行的进程，并恢复当前阶段的定时任务
+    ///
+    /// 控制命令继续执行当前阶段剩余的命令，等待中的自动重启重新计时
+    pub fn coldplug(service: &mut ServiceUnit) {
+        let id = service.unit_id();
+        let sub_state = *service.unit_base().sub_state();
+        let main = service.control_mut().reexec_main.take();
+        let control = service.control_mut().reexec_control.take();
+
+        match sub_state {
+            UnitSubState::AutoRestart => {
+                let _ = Self::restart(service);
+                return;
+            }
+            UnitSubState::Dead
+                if *service.unit_base().state() == UnitState::Active
+                    && service.service_part().remain_after_exit() =>
+            {
+                UnitManager::push_flag_running(id);
+                return;
+            }
+            _ => {}
+        }
+        if main.is_none() && control.is_none() {
+            return;
+        }
+
+        ProcessManager::watch_unit(id, *service.service_part().kill_context());
+        UnitManager::acquire_tty(id, service.service_part().exec_context().stdio());
+        if let Some(pid) = main {
+            if ProcessManager::adopt(pid, ProcessOwner::Main(id)) {
+                UnitManager::push_running(id, pid);
+            }
+        }
+        if let Some((pid, index)) = control {
+            let cmds = service.service_part().phase_commands(sub_state);
+            if index < cmds.len() && ProcessManager::adopt(pid, ProcessOwner::Control(id)) {
+                let mut cmd = cmds[index].clone();
+                let pending = cmds[index + 1..].to_vec();
+                cmd.pid = pid.as_raw() as u32;
+                let control = service.control_mut();
+                control.running = Some(cmd);
+                control.pending = pending.into();
+                Self::arm_phase_timeout(service);
+            }
+        }
+        // 正在等待进程退出的服务重新发送KillSignal并计时
+        if sub_state == UnitSubState::StopSigterm {
+            let ns = service.service_part().timeout_stop_sec();
+            ProcessManager::stop_unit(id, Duration::from_nanos(ns));
+        }
+    }
+
     /// ## 显示退出Service
+    ///
+    /// 依次执行ExecStop、终止服务的进程、执行ExecStopPost，上一阶段的进程退出被分发后才进入下一阶段
     pub fn exit(service: &mut ServiceUnit) {
         // TODO: 打印日志
-        let _ = Self::exec_stop(service);
-
-        let ns = service.service_part().timeout_stop_sec();
         let id = service.unit_id();
-        if ns != 0 {
-            // 计时器触发后若服务还未停止，则kill掉进程
-            TimerManager::push_timer(
-                Duration::from_nanos(ns),
-                move || {
-                    UnitManager::try_kill_running(id);
-                    Ok(())
-                },
-                service.unit_id(),
-            )
+        match *service.unit_base().sub_state() {
+            UnitSubState::StartPre
+            | UnitSubState::StartPost
+            | UnitSubState::Running
+            | UnitSubState::Reload => {}
+            // 取消等待中的自动重启
+            UnitSubState::AutoRestart => {
+                TimerManager::cancel_timer(id);
+                service.unit_base_mut().set_sub_state(UnitSubState::Dead);
+                service.unit_base_mut().set_state(UnitState::Inactive);
+                return;
+            }
+            // 正在停止或未运行
+            _ => return,
+        }
+
+        // 取消当前阶段的超时定时器
+        TimerManager::cancel_timer(id);
+        service.control_mut().result = Some(ExitStatus::Abort);
+        if UnitManager::running_pid(id).is_some() {
+            Self::enter_stop(service);
         } else {
-            UnitManager::try_kill_running(id);
+            Self::enter_stop_post(service);
         }
     }
 }
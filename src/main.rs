@@ -7,67 +7,90 @@ mod systemctl;
 mod task;
 mod time;
 mod unit;
-use crate::executor::Executor;
 use error::ErrorFormat;
-use manager::{timer_manager::TimerManager, Manager};
+use executor::dep_graph::DepGraph;
+use manager::{
+    event_loop::EventLoop,
+    install_manager::InstallManager,
+    job_manager::{JobManager, JobMode, JobType},
+    manager_config::ManagerConfig,
+    reexec_manager::ReexecManager,
+    UnitManager,
+};
 use parse::UnitParser;
-use std::thread;
 use systemctl::listener::Systemctl;
 use unit::signal::init_signal_handler;
 
 pub struct FileDescriptor(usize);
 
-const DRAGON_REACH_UNIT_DIR: &'static str = "/etc/reach/system/";
+const DRAGON_REACH_CONF: &str = "/etc/reach/system.conf";
 fn main() {
     // 初始化
+    // 屏蔽由主循环通过signalfd处理的信号，需在启动任何服务之前完成
+    init_signal_handler();
     Systemctl::init();
+    ManagerConfig::load();
+
+    // 由daemon-reexec重新执行时读取之前序列化的状态
+    let serialized = ReexecManager::deserialize();
 
-    let mut units_file_name = Vec::new();
-    //读取目录里面的unit文件
-    if let Ok(entries) = std::fs::read_dir(DRAGON_REACH_UNIT_DIR) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_file() {
-                        let filename = entry.file_name().to_str().unwrap().to_string();
-                        units_file_name.push(filename);
-                    }
-                }
-            }
+    //加载启动目标，其依赖的Unit在解析时一并加载
+    let default_unit = default_unit();
+    let id = match UnitParser::from_path(&default_unit) {
+        Ok(id) => {
+            println!("Parse {} success!", default_unit);
+            Some(id)
+        }
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", default_unit, e.error_format());
+            None
         }
+    };
+
+    // 加载重新执行之前已加载的Unit
+    if let Some(units) = &serialized {
+        ReexecManager::load_units(units);
     }
 
-    //启动服务
-    for path in units_file_name {
-        let id = match UnitParser::from_path(&path) {
-            Ok(id) => id,
-            Err(e) => {
-                eprintln!("Err:{}", e.error_format());
-                0
-            }
-        };
-        if id != 0 {
-            if let Err(e) = Executor::exec(id) {
-                eprintln!("Err:{}", e.error_format());
-            }
+    // 推导反向依赖
+    UnitManager::init_units_dependencies();
+
+    // 报告已加载Unit之间的排序环
+    DepGraph::check_loaded_units();
+
+    //为启动目标提交启动作业，由主循环按照依赖关系调度启动
+    //重新执行时恢复各Unit的状态并接管运行中的进程，不再启动启动目标
+    if let Some(units) = serialized {
+        ReexecManager::restore(units);
+    } else if let Some(id) = id {
+        if let Err(e) = JobManager::add_job(id, JobType::Start, JobMode::Replace) {
+            eprintln!("Err:{}", e.error_format());
         }
-        println!("Parse {} success!", path);
     }
 
-    // 初始化信号处理程序
-    init_signal_handler();
-    // 监听systemctl
-    thread::spawn(move || {
-        Systemctl::ctl_listen();
-    });
+    // 不作为1号进程运行时成为子进程收割者，使孤儿进程同样托管给DragonReach回收
+    unsafe {
+        libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1);
+    }
 
     // 启动完服务后进入主循环
-    loop {
-        // 检查各服务运行状态
-        Manager::check_running_status();
-        // 检查cmd进程状态
-        Manager::check_cmd_proc();
-        // 检查计时器任务
-        TimerManager::check_timer();
+    let mut event_loop = EventLoop::new().expect("Failed to create event loop");
+    event_loop.run();
+}
+
+/// ## 获取启动时需要启动的Unit
+///
+/// 优先使用内核命令行中的systemd.unit=，否则使用default.target，
+/// default.target为符号链接时使用其指向的Unit，不存在时使用multi-user.target
+fn default_unit() -> String {
+    if let Ok(cmdline) = std::fs::read_to_string("/proc/cmdline") {
+        let unit = cmdline
+            .split_whitespace()
+            .find_map(|arg| arg.strip_prefix("systemd.unit="));
+        if let Some(unit) = unit {
+            return unit.to_string();
+        }
     }
+
+    InstallManager::default_target()
 }
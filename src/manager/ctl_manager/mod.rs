@@ -6,14 +6,20 @@ use std::sync::{Arc, Mutex};
 use crate::error::runtime_error::RuntimeError;
 use crate::error::runtime_error::RuntimeErrorType;
 use crate::error::ErrorFormat;
+use crate::parse::builtin::builtin_unit;
+use crate::parse::lookup::UnitLookup;
 use crate::parse::parse_util::UnitParseUtil;
 use crate::systemctl::ctl_parser::{CommandOperation, Pattern};
 use crate::systemctl::ctl_path;
 use crate::systemctl::listener::Command;
 use crate::unit::Unit;
-use crate::unit::UnitState;
+use crate::unit::{LoadState, UnitState};
 
-use super::{UnitManager, ID_TO_UNIT_MAP};
+use super::install_manager::{InstallChanges, InstallManager, PresetMode, UnitFileState};
+use super::job_manager::{JobManager, JobMode, JobType};
+use super::reexec_manager::ReexecManager;
+use super::shutdown_manager::{ShutdownAction, ShutdownManager};
+use super::{Manager, UnitManager, ID_TO_UNIT_MAP};
 pub struct CtlManager;
 
 lazy_static! {
@@ -28,14 +34,14 @@ impl CtlManager {
         // TODO:目前假设一个时刻只有一个进程使用systemdctl,后续应该使用DBus等更灵活的进程通信方式
         match cmd.operation {
             CommandOperation::ListUnits => Self::list_unit(cmd.patterns),
-            CommandOperation::Start => Self::start(cmd.args.unwrap()),
-            CommandOperation::Restart => Self::restart(cmd.args.unwrap(), false),
-            CommandOperation::Stop => Self::stop(cmd.args.unwrap()),
-            CommandOperation::Reboot => Ok(Self::reboot()),
+            CommandOperation::Start => Self::start(cmd.args.unwrap(), cmd.patterns),
+            CommandOperation::Restart => Self::restart(cmd.args.unwrap(), false, cmd.patterns),
+            CommandOperation::Stop => Self::stop(cmd.args.unwrap(), cmd.patterns),
+            CommandOperation::Reboot => Self::shutdown(ShutdownAction::Reboot, cmd.patterns),
             CommandOperation::ListSockets => todo!(),
             CommandOperation::ListTimers => todo!(),
-            CommandOperation::Reload => todo!(),
-            CommandOperation::TryRestart => Self::restart(cmd.args.unwrap(), true),
+            CommandOperation::Reload => Self::reload(cmd.args.unwrap(), cmd.patterns),
+            CommandOperation::TryRestart => Self::restart(cmd.args.unwrap(), true, cmd.patterns),
             CommandOperation::ReloadOrRestart => todo!(),
             CommandOperation::ReloadOrTryRestart => todo!(),
             CommandOperation::Isolate => todo!(),
@@ -50,47 +56,61 @@ impl CtlManager {
                 patterns.push(Pattern::State(UnitState::Failed));
                 Self::list_unit(patterns)
             }
-            CommandOperation::Status => todo!(),
-            CommandOperation::Show => todo!(),
-            CommandOperation::Cat => todo!(),
+            CommandOperation::Status => Self::status(cmd.args.unwrap_or_default()),
+            CommandOperation::Show => Self::show(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::Cat => Self::cat(cmd.args.unwrap_or_default()),
             CommandOperation::SetProperty => todo!(),
             CommandOperation::Help => todo!(),
             CommandOperation::ResetFailed => todo!(),
             CommandOperation::ListDependencies => todo!(),
             CommandOperation::ListUnitFiles => todo!(),
-            CommandOperation::Enable => todo!(),
-            CommandOperation::Disable => todo!(),
-            CommandOperation::Reenable => todo!(),
-            CommandOperation::Preset => todo!(),
-            CommandOperation::PresetAll => todo!(),
-            CommandOperation::IsEnabled => todo!(),
-            CommandOperation::Mask => todo!(),
-            CommandOperation::UnMask => todo!(),
-            CommandOperation::Link => todo!(),
-            CommandOperation::AddWants => todo!(),
-            CommandOperation::AddRequires => todo!(),
+            CommandOperation::Enable => Self::enable(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::Disable => Self::disable(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::Reenable => {
+                Self::reenable(cmd.args.unwrap_or_default(), cmd.patterns)
+            }
+            CommandOperation::Preset => Self::preset(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::PresetAll => Self::preset_all(cmd.patterns),
+            CommandOperation::IsEnabled => Self::is_enabled(cmd.args.unwrap_or_default()),
+            CommandOperation::Mask => Self::mask(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::UnMask => Self::unmask(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::Link => Self::link(cmd.args.unwrap_or_default(), cmd.patterns),
+            CommandOperation::AddWants => {
+                Self::add_dependency(cmd.args.unwrap_or_default(), "wants", cmd.patterns)
+            }
+            CommandOperation::AddRequires => {
+                Self::add_dependency(cmd.args.unwrap_or_default(), "requires", cmd.patterns)
+            }
             CommandOperation::Edit => todo!(),
-            CommandOperation::GetDefault => todo!(),
-            CommandOperation::SetDefault => todo!(),
+            CommandOperation::GetDefault => {
+                println!("{}", InstallManager::default_target());
+                Ok(())
+            }
+            CommandOperation::SetDefault => {
+                Self::set_default(cmd.args.unwrap_or_default(), cmd.patterns)
+            }
             CommandOperation::ListMachines => todo!(),
-            CommandOperation::ListJobs => todo!(),
-            CommandOperation::Cancel => todo!(),
+            CommandOperation::ListJobs => Self::list_jobs(),
+            CommandOperation::Cancel => Self::cancel(cmd.args.unwrap_or_default()),
             CommandOperation::Snapshot => todo!(),
             CommandOperation::Delete => todo!(),
             CommandOperation::ShowEnvironment => todo!(),
             CommandOperation::SetEnvironment => todo!(),
             CommandOperation::UnsetEnvironment => todo!(),
             CommandOperation::ImportEnvironment => todo!(),
-            CommandOperation::DeamonReload => todo!(),
-            CommandOperation::DeamonReexec => todo!(),
+            CommandOperation::DeamonReload => {
+                Manager::reload_units();
+                Ok(())
+            }
+            CommandOperation::DeamonReexec => ReexecManager::reexec(),
             CommandOperation::IsSystemRunning => todo!(),
             CommandOperation::Default => todo!(),
             CommandOperation::Rescue => todo!(),
             CommandOperation::Emergency => todo!(),
-            CommandOperation::Halt => todo!(),
-            CommandOperation::Poweroff => todo!(),
-            CommandOperation::Kexec => todo!(),
-            CommandOperation::Exit => todo!(),
+            CommandOperation::Halt => Self::shutdown(ShutdownAction::Halt, cmd.patterns),
+            CommandOperation::Poweroff => Self::shutdown(ShutdownAction::Poweroff, cmd.patterns),
+            CommandOperation::Kexec => Self::shutdown(ShutdownAction::Kexec, cmd.patterns),
+            CommandOperation::Exit => Self::shutdown(ShutdownAction::Exit, cmd.patterns),
             CommandOperation::SwitchRoot => todo!(),
             CommandOperation::Suspend => todo!(),
             CommandOperation::Hibernate => todo!(),
@@ -119,75 +139,381 @@ impl CtlManager {
         Ok(())
     }
 
-    pub fn stop(names: Vec<String>) -> Result<(), RuntimeError> {
-        // TODO:打日志
+    /// ## 以Key=Value的形式输出Unit的属性
+    ///
+    /// 可通过--property=A,B只输出指定的属性
+    pub fn show(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mut filter = Vec::new();
+        for pat in patterns {
+            if let Pattern::Property(p) = pat {
+                filter.extend(p.split(',').map(|s| s.to_string()));
+            }
+        }
+
+        let mut res = Vec::new();
         for name in names {
-            match UnitManager::get_unit_with_name(&name) {
-                Some(unit) => {
-                    unit.lock().unwrap().exit();
-                }
+            let unit = match UnitManager::get_unit_with_name(&name) {
+                Some(unit) => unit,
                 None => {
                     eprintln!("{} is not a unit", name);
                     return Err(RuntimeError::new(RuntimeErrorType::FileNotFound));
                 }
+            };
+            let props = unit.lock().unwrap().properties();
+            let mut lines = Vec::new();
+            for (key, val) in props {
+                if filter.is_empty() || filter.contains(&key) {
+                    lines.push(format!("{}={}", key, val));
+                }
             }
+            res.push(lines.join("\n"));
         }
+
+        println!("{}", res.join("\n\n"));
         Ok(())
     }
 
-    pub fn start(names: Vec<String>) -> Result<(), RuntimeError> {
-        // TODO:打日志
+    /// ## 输出Unit的状态及加载该Unit的文件
+    pub fn status(names: Vec<String>) -> Result<(), RuntimeError> {
+        let mut res = Vec::new();
         for name in names {
-            match UnitManager::get_unit_with_name(&name) {
-                Some(unit) => unit.lock().unwrap().run()?,
-                None => match UnitParseUtil::parse_unit_no_type(&name) {
-                    Ok(i) => {
-                        let unit = UnitManager::get_unit_with_id(&i).unwrap();
-                        let mut unit = unit.lock().unwrap();
-                        unit.run()?;
-                    }
-                    Err(err) => {
-                        eprintln!("parse unit {} error :{}", name, err.error_format());
-                    }
-                },
+            let id = Self::unit_id(&name)?;
+            let unit = UnitManager::get_unit_with_id(&id).unwrap();
+            let unit = unit.lock().unwrap();
+            let base = unit.unit_base();
+            let fragment = match base.fragment_path() {
+                "" => "built-in".to_string(),
+                path => path.to_string(),
+            };
+            let loaded = match base.load_state() {
+                LoadState::Error => format!("error (Reason: {})", base.load_error()),
+                LoadState::Loaded => format!("loaded ({})", fragment),
+                state => state.to_string(),
+            };
+            let mut lines = vec![
+                format!("{} - {}", base.unit_name(), base.unit_part().description()),
+                format!("     Loaded: {}", loaded),
+                format!(
+                    "     Active: {} ({})",
+                    base.state().to_string(),
+                    base.sub_state().to_string()
+                ),
+            ];
+            if let Some(pid) = UnitManager::running_pid(id) {
+                lines.push(format!("   Main PID: {}", pid));
             }
+            res.push(lines.join("\n"));
         }
+        println!("{}", res.join("\n\n"));
         Ok(())
     }
 
-    pub fn reboot() {
-        unsafe { libc::syscall(libc::SYS_reboot, 0, 0, 0, 0, 0, 0) };
+    /// ## 输出Unit文件的内容，每个文件之前输出其路径
+    pub fn cat(names: Vec<String>) -> Result<(), RuntimeError> {
+        let mut res = Vec::new();
+        for name in names {
+            let path = match UnitManager::get_unit_with_name(&name) {
+                Some(unit) => unit.lock().unwrap().unit_base().fragment_path().to_string(),
+                None => UnitLookup::find(&name).unwrap_or_default(),
+            };
+            let content = match path.as_str() {
+                "" => {
+                    builtin_unit(&name).map(|content| ("built-in".to_string(), content.to_string()))
+                }
+                path => std::fs::read_to_string(path)
+                    .ok()
+                    .map(|content| (path.to_string(), content)),
+            };
+            match content {
+                Some((path, content)) => res.push(format!("# {}\n{}", path, content)),
+                None => {
+                    eprintln!("No files found for {}", name);
+                    return Err(RuntimeError::new(RuntimeErrorType::FileNotFound));
+                }
+            }
+        }
+        println!("{}", res.join("\n"));
+        Ok(())
     }
 
-    pub fn restart(names: Vec<String>, is_try: bool) -> Result<(), RuntimeError> {
-        // TODO:打日志
+    pub fn stop(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mode = Self::job_mode(&patterns)?;
         for name in names {
-            match UnitManager::get_unit_with_name(&name) {
-                Some(unit) => {
-                    let mut unit = unit.lock().unwrap();
-                    if is_try && *unit.unit_base().state() == UnitState::Active {
-                        unit.restart()?;
-                    } else {
-                        unit.restart()?;
-                    }
+            let id = Self::unit_id(&name)?;
+            JobManager::add_job(id, JobType::Stop, mode)?;
+        }
+        Ok(())
+    }
+
+    pub fn reload(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mode = Self::job_mode(&patterns)?;
+        for name in names {
+            let id = Self::unit_id(&name)?;
+            JobManager::add_job(id, JobType::Reload, mode)?;
+        }
+        Ok(())
+    }
+
+    pub fn start(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mode = Self::job_mode(&patterns)?;
+        for name in names {
+            let id = Self::unit_id(&name)?;
+            JobManager::add_job(id, JobType::Start, mode)?;
+        }
+        Ok(())
+    }
+
+    /// ## 开始关机流程，--force跳过停止Unit的阶段
+    pub fn shutdown(action: ShutdownAction, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let force = patterns.iter().any(|p| matches!(p, Pattern::Force));
+        ShutdownManager::begin(action, force)
+    }
+
+    /// ## 按照[Install]段启用Unit，--now同时启动Unit
+    pub fn enable(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::enable(&names, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret?;
+        if patterns.iter().any(|p| matches!(p, Pattern::Now)) {
+            return Self::start(names, patterns);
+        }
+        Ok(())
+    }
+
+    /// ## 删除启用Unit时创建的符号链接，--now同时停止Unit
+    pub fn disable(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::disable(&names, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret?;
+        if patterns.iter().any(|p| matches!(p, Pattern::Now)) {
+            return Self::stop(names, patterns);
+        }
+        Ok(())
+    }
+
+    /// ## 先禁用再重新启用Unit，用于[Install]段修改之后
+    pub fn reenable(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::disable(&names, runtime, &mut changes)
+            .and_then(|_| InstallManager::enable(&names, runtime, &mut changes));
+        Self::apply_install_changes(&changes, &patterns);
+        ret?;
+        if patterns.iter().any(|p| matches!(p, Pattern::Now)) {
+            return Self::restart(names, false, patterns);
+        }
+        Ok(())
+    }
+
+    /// ## 屏蔽Unit，--runtime只屏蔽到重启为止，--now同时停止Unit
+    pub fn mask(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::mask(&names, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret?;
+        if patterns.iter().any(|p| matches!(p, Pattern::Now)) {
+            return Self::stop(names, patterns);
+        }
+        Ok(())
+    }
+
+    /// ## 取消屏蔽Unit
+    pub fn unmask(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::unmask(&names, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// ## 将搜索路径之外的Unit文件链接到配置目录中
+    pub fn link(paths: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::link(&paths, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// ## add-wants、add-requires，第一个参数为目标Unit，其余为被依赖的Unit
+    pub fn add_dependency(
+        args: Vec<String>,
+        suffix: &str,
+        patterns: Vec<Pattern>,
+    ) -> Result<(), RuntimeError> {
+        if args.len() < 2 {
+            eprintln!("Expected a target and at least one unit");
+            return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+        }
+        let runtime = patterns.iter().any(|p| matches!(p, Pattern::Runtime));
+        let mut changes = InstallChanges::default();
+        let ret =
+            InstallManager::add_dependency(&args[0], &args[1..], suffix, runtime, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// ## 设置默认启动的目标
+    pub fn set_default(args: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let name = match args.as_slice() {
+            [name] => name,
+            _ => {
+                eprintln!("Expected exactly one target");
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+            }
+        };
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::set_default(name, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// ## 按照预设策略启用或禁用Unit
+    pub fn preset(names: Vec<String>, patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mode = Self::preset_mode(&patterns)?;
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::preset(&names, mode, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// ## 按照预设策略启用或禁用所有Unit
+    pub fn preset_all(patterns: Vec<Pattern>) -> Result<(), RuntimeError> {
+        let mode = Self::preset_mode(&patterns)?;
+        let mut changes = InstallChanges::default();
+        let ret = InstallManager::preset_all(mode, &mut changes);
+        Self::apply_install_changes(&changes, &patterns);
+        ret
+    }
+
+    /// 获取--preset-mode=指定的模式，未指定时为full
+    fn preset_mode(patterns: &[Pattern]) -> Result<PresetMode, RuntimeError> {
+        for pat in patterns {
+            if let Pattern::PresetMode(mode) = pat {
+                return PresetMode::parse(mode);
+            }
+        }
+        Ok(PresetMode::default())
+    }
+
+    /// ## 输出Unit的启用状态，有未启用的Unit时返回错误
+    pub fn is_enabled(names: Vec<String>) -> Result<(), RuntimeError> {
+        let mut enabled = true;
+        for name in names {
+            let state = InstallManager::unit_file_state(&name)?;
+            println!("{}", state);
+            enabled &= !matches!(state, UnitFileState::Disabled | UnitFileState::Masked);
+        }
+        if !enabled {
+            return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
+        }
+        Ok(())
+    }
+
+    /// 输出对单元目录的修改，有修改且未指定--no-reload时重新加载Unit
+    fn apply_install_changes(changes: &InstallChanges, patterns: &[Pattern]) {
+        changes.report();
+        if !changes.is_empty() && !patterns.iter().any(|p| matches!(p, Pattern::NoReload)) {
+            Manager::reload_units();
+        }
+    }
+
+    /// ## 重启Unit，try-restart只重启处于运行状态的Unit
+    pub fn restart(
+        names: Vec<String>,
+        is_try: bool,
+        patterns: Vec<Pattern>,
+    ) -> Result<(), RuntimeError> {
+        let mode = Self::job_mode(&patterns)?;
+        for name in names {
+            let id = Self::unit_id(&name)?;
+            if is_try && !JobManager::is_active(id) {
+                continue;
+            }
+            JobManager::add_job(id, JobType::Restart, mode)?;
+        }
+        Ok(())
+    }
+
+    /// ## 列出所有已安装的作业
+    pub fn list_jobs() -> Result<(), RuntimeError> {
+        let jobs = JobManager::jobs();
+        let mut res = "JOB\tUNIT\t\t\t\tTYPE\t\tSTATE".to_string();
+        for job in jobs.iter() {
+            let name = match UnitManager::get_unit_with_id(&job.unit()) {
+                Some(unit) => unit.lock().unwrap().unit_base().unit_name(),
+                None => job.unit().to_string(),
+            };
+            res = format!(
+                "{}\n{}\t{}\t\t\t{}\t\t{}",
+                res,
+                job.id(),
+                name,
+                job.job_type(),
+                job.state()
+            );
+        }
+        res = format!("{}\n\n{} jobs listed.", res, jobs.len());
+        println!("{}", res);
+        Ok(())
+    }
+
+    /// ## 取消指定id的作业，未指定时取消所有作业
+    pub fn cancel(ids: Vec<String>) -> Result<(), RuntimeError> {
+        if ids.is_empty() {
+            JobManager::cancel_all();
+            return Ok(());
+        }
+        for id in ids {
+            let id = match id.parse::<usize>() {
+                Ok(id) => id,
+                Err(_) => {
+                    eprintln!("Failed to parse job id: {}", id);
+                    return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
                 }
-                None => match UnitParseUtil::parse_unit_no_type(&name) {
-                    Ok(i) => {
-                        let unit = UnitManager::get_unit_with_id(&i).unwrap();
-                        unit.lock().unwrap().run()?;
-                    }
-                    Err(err) => {
-                        eprintln!("parse unit {} error :{}", name, err.error_format());
-                        return Err(RuntimeError::new(RuntimeErrorType::InvalidFileFormat));
-                    }
-                },
+            };
+            if !JobManager::cancel(id) {
+                eprintln!("Job {} does not exist", id);
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
             }
         }
         Ok(())
     }
 
+    /// 获取--job-mode=指定的作业模式，未指定时为replace
+    fn job_mode(patterns: &[Pattern]) -> Result<JobMode, RuntimeError> {
+        for pat in patterns {
+            if let Pattern::JobMode(mode) = pat {
+                return JobMode::parse(mode);
+            }
+        }
+        Ok(JobMode::default())
+    }
+
+    /// 通过名称获取Unit的id，Unit未加载时尝试解析对应的Unit文件
+    fn unit_id(name: &str) -> Result<usize, RuntimeError> {
+        if let Some(id) = UnitManager::get_id_with_path(name) {
+            return Ok(id);
+        }
+        match UnitParseUtil::parse_unit_no_type(name) {
+            Ok(id) => {
+                UnitManager::init_units_dependencies();
+                Ok(id)
+            }
+            Err(err) => {
+                eprintln!("parse unit {} error :{}", name, err.error_format());
+                Err(RuntimeError::new(RuntimeErrorType::FileNotFound))
+            }
+        }
+    }
+
     pub fn init_ctl_writer() -> File {
-        let fd = unsafe { libc::open(ctl_path().as_ptr(), libc::O_WRONLY) };
+        let fd = unsafe { libc::open(ctl_path().as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
         if fd < 0 {
             panic!("open ctl pipe error");
         }
@@ -203,6 +529,8 @@ impl CtlManager {
         for unit in bindings {
             units.push(unit.clone());
         }
+        drop(reader);
+        let mut all = false;
         for pat in patterns {
             match pat {
                 Pattern::Type(t) => {
@@ -217,12 +545,23 @@ impl CtlManager {
                         .filter(|x| *x.lock().unwrap().unit_base().state() == s)
                         .collect::<Vec<_>>()
                 }
+                Pattern::All => all = true,
                 Pattern::None => {}
                 _ => {
                     return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
                 }
             }
         }
+        // 未指定--all时不显示未能加载且未运行的Unit，如只被引用而不存在的Unit
+        if !all {
+            units.retain(|unit| {
+                let unit = unit.lock().unwrap();
+                let base = unit.unit_base();
+                *base.load_state() == LoadState::Loaded
+                    || *base.state() != UnitState::Inactive
+                    || JobManager::unit_job(unit.unit_id()).is_some()
+            });
+        }
         Ok(units)
     }
 }
@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    os::fd::{AsRawFd, RawFd},
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    sys::{
+        epoll::{
+            epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+        },
+        signal::Signal,
+        signalfd::{SfdFlags, SignalFd},
+        time::TimeSpec,
+        timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags},
+    },
+    unistd::{close, getpid},
+};
+
+use crate::{systemctl::listener::Systemctl, unit::signal::manager_sigset};
+
+use super::{
+    job_manager::JobManager,
+    manager_config::ManagerConfig,
+    shutdown_manager::{ShutdownAction, ShutdownManager},
+    timer_manager::TimerManager,
+    Manager,
+};
+
+/// epoll事件中用于区分事件来源的标识
+const SIGNAL_TOKEN: u64 = 0;
+const TIMER_TOKEN: u64 = 1;
+const CTL_TOKEN: u64 = 2;
+
+/// ## DragonReach的主循环
+///
+/// 通过一个epoll同时等待信号(signalfd)、最近的计时器(timerfd)以及systemctl控制管道，
+/// 没有事件时主循环睡眠，不再忙等
+pub struct EventLoop {
+    epoll: RawFd,
+    signal_fd: SignalFd,
+    timer_fd: TimerFd,
+    ctl: File,
+}
+
+impl EventLoop {
+    /// ## 创建主循环
+    ///
+    /// 调用前需已通过init_signal_handler屏蔽相关信号
+    pub fn new() -> Result<EventLoop, Errno> {
+        let epoll = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?;
+        let signal_fd = SignalFd::with_flags(
+            &manager_sigset(),
+            SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC,
+        )?;
+        let timer_fd = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+        )?;
+        let ctl = Systemctl::init_listener();
+
+        let event_loop = EventLoop {
+            epoll,
+            signal_fd,
+            timer_fd,
+            ctl,
+        };
+        event_loop.add(event_loop.signal_fd.as_raw_fd(), SIGNAL_TOKEN)?;
+        event_loop.add(event_loop.timer_fd.as_raw_fd(), TIMER_TOKEN)?;
+        event_loop.add(event_loop.ctl.as_raw_fd(), CTL_TOKEN)?;
+        Ok(event_loop)
+    }
+
+    fn add(&self, fd: RawFd, token: u64) -> Result<(), Errno> {
+        let mut event = EpollEvent::new(EpollFlags::EPOLLIN, token);
+        epoll_ctl(self.epoll, EpollOp::EpollCtlAdd, fd, &mut event)
+    }
+
+    /// ## 运行主循环
+    pub fn run(&mut self) -> ! {
+        let mut events = [EpollEvent::empty(); 8];
+        loop {
+            // 检查计时器任务，调度作业后按最近的到期时间设置timerfd
+            TimerManager::check_timer();
+            JobManager::dispatch();
+            // 关机事务中的作业全部完成后结束关机
+            ShutdownManager::check();
+            self.arm_timer();
+
+            let count = match epoll_wait(self.epoll, &mut events, -1) {
+                Ok(count) => count,
+                Err(Errno::EINTR) => continue,
+                Err(e) => {
+                    eprintln!("epoll_wait error: {}", e);
+                    continue;
+                }
+            };
+
+            for event in &events[..count] {
+                match event.data() {
+                    SIGNAL_TOKEN => self.handle_signals(),
+                    // 仅清除到期计数，计时器在下一轮循环开始时检查
+                    TIMER_TOKEN => {
+                        let _ = self.timer_fd.wait();
+                    }
+                    CTL_TOKEN => Systemctl::handle_ctl(&mut self.ctl),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// 按照最近的计时器到期时间设置timerfd，没有计时器时关闭timerfd
+    fn arm_timer(&self) {
+        let deadline = match TimerManager::next_deadline() {
+            Some(deadline) => deadline,
+            None => {
+                let _ = self.timer_fd.unset();
+                return;
+            }
+        };
+        // 到期时间为0会关闭timerfd，已到期的计时器至少等待1ns
+        let timeout = deadline
+            .saturating_duration_since(Instant::now())
+            .max(Duration::from_nanos(1));
+        if let Err(e) = self.timer_fd.set(
+            Expiration::OneShot(TimeSpec::from_duration(timeout)),
+            TimerSetTimeFlags::empty(),
+        ) {
+            eprintln!("timerfd set error: {}", e);
+        }
+    }
+
+    fn handle_signals(&mut self) {
+        let mut child_exited = false;
+        loop {
+            let info = match self.signal_fd.read_signal() {
+                Ok(Some(info)) => info,
+                Ok(None) => break,
+                Err(Errno::EINTR) => continue,
+                Err(e) => {
+                    eprintln!("signalfd read error: {}", e);
+                    break;
+                }
+            };
+            match Signal::try_from(info.ssi_signo as i32) {
+                Ok(Signal::SIGCHLD) => child_exited = true,
+                Ok(Signal::SIGHUP) => ManagerConfig::load(),
+                // 作为1号进程时SIGINT来自ctrl-alt-del
+                Ok(Signal::SIGINT) if getpid().as_raw() == 1 => ShutdownManager::ctrl_alt_del(),
+                Ok(Signal::SIGTERM) if getpid().as_raw() == 1 => {
+                    eprintln!("received SIGTERM, stopping all units");
+                    Manager::stop_all_units();
+                }
+                Ok(signal @ (Signal::SIGTERM | Signal::SIGINT)) => {
+                    eprintln!("received {}, shutting down", signal);
+                    let _ = ShutdownManager::begin(ShutdownAction::Exit, false);
+                }
+                _ => {}
+            }
+        }
+        // 多个SIGCHLD可能合并为一个，回收时会处理所有退出的子进程
+        if child_exited {
+            Manager::check_running_status();
+        }
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        let _ = close(self.epoll);
+    }
+}
@@ -0,0 +1,795 @@
+use std::{ffi::CString, fmt, fs, os::unix::fs::symlink, path::Path};
+
+use crate::{
+    error::runtime_error::{RuntimeError, RuntimeErrorType},
+    parse::{
+        lookup::{UnitLookup, ADMIN_UNIT_DIR, RUNTIME_UNIT_DIR, UNIT_SEARCH_PATH},
+        parse_util::UnitParseUtil,
+        UnitParser,
+    },
+    unit::{LoadState, UnitType},
+};
+
+use super::UnitManager;
+
+/// 屏蔽Unit的链接所指向的文件
+const MASK_TARGET: &str = "/dev/null";
+/// 启动时默认启动的目标
+pub const DEFAULT_TARGET: &str = "default.target";
+/// 未设置default.target时启动的目标
+pub const FALLBACK_TARGET: &str = "multi-user.target";
+/// 预设策略文件目录，按优先级从高到低排列，同名文件只读取优先级最高的
+const PRESET_DIRS: &[&str] = &["/etc/reach/system-preset/", "/usr/lib/reach/system-preset/"];
+
+/// 对单元目录的一项修改
+pub enum InstallChange {
+    // 创建了符号链接，.0为链接路径，.1为指向的文件
+    Symlink(String, String),
+    // 删除了符号链接
+    Unlink(String),
+}
+
+impl fmt::Display for InstallChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallChange::Symlink(path, target) => {
+                write!(f, "Created symlink {} → {}.", path, target)
+            }
+            InstallChange::Unlink(path) => write!(f, "Removed \"{}\".", path),
+        }
+    }
+}
+
+/// ## 安装操作对单元目录的修改
+///
+/// 所有修改单元目录中符号链接的命令都通过它创建及删除符号链接，并统一输出所做的修改
+#[derive(Default)]
+pub struct InstallChanges {
+    changes: Vec<InstallChange>,
+}
+
+impl InstallChanges {
+    /// ## 创建符号链接，所在目录不存在时一并创建
+    ///
+    /// 链接已存在且指向同一文件时不做修改，指向其他文件时返回错误
+    pub fn symlink(&mut self, path: &str, target: &str) -> Result<(), RuntimeError> {
+        if let Ok(old) = fs::read_link(path) {
+            if old == Path::new(target) {
+                return Ok(());
+            }
+            eprintln!(
+                "Failed to create symlink {}: already exists and points to {}",
+                path,
+                old.display()
+            );
+            return Err(RuntimeError::new(RuntimeErrorType::FileAccessDenied));
+        }
+        if Path::new(path).exists() {
+            eprintln!("Failed to create symlink {}: file exists", path);
+            return Err(RuntimeError::new(RuntimeErrorType::FileAccessDenied));
+        }
+        if let Some(dir) = Path::new(path).parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(e) = symlink(target, path) {
+            eprintln!("Failed to create symlink {}: {}", path, e);
+            return Err(RuntimeError::new(RuntimeErrorType::FileAccessDenied));
+        }
+        self.changes
+            .push(InstallChange::Symlink(path.to_string(), target.to_string()));
+        Ok(())
+    }
+
+    /// ## 删除符号链接，不是符号链接的文件不会被删除
+    pub fn unlink(&mut self, path: &str) -> Result<(), RuntimeError> {
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_symlink() => {}
+            _ => return Ok(()),
+        }
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("Failed to remove {}: {}", path, e);
+            return Err(RuntimeError::new(RuntimeErrorType::FileAccessDenied));
+        }
+        self.changes.push(InstallChange::Unlink(path.to_string()));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// 输出所有修改
+    pub fn report(&self) {
+        for change in self.changes.iter() {
+            println!("{}", change);
+        }
+    }
+}
+
+/// Unit的启用状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitFileState {
+    Enabled,
+    // 只在运行时目录中启用
+    EnabledRuntime,
+    Disabled,
+    // 没有[Install]段，不能被启用
+    Static,
+    Masked,
+    // 以符号链接定义的别名
+    Alias,
+    // [Install]段中只有Also=
+    Indirect,
+}
+
+impl fmt::Display for UnitFileState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            UnitFileState::Enabled => "enabled",
+            UnitFileState::EnabledRuntime => "enabled-runtime",
+            UnitFileState::Disabled => "disabled",
+            UnitFileState::Static => "static",
+            UnitFileState::Masked => "masked",
+            UnitFileState::Alias => "alias",
+            UnitFileState::Indirect => "indirect",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 从Unit的[Install]段得到的安装信息
+struct InstallInfo {
+    name: String,
+    fragment_path: String,
+    wanted_by: Vec<String>,
+    required_by: Vec<String>,
+    also: Vec<String>,
+    alias: Vec<String>,
+}
+
+impl InstallInfo {
+    fn is_static(&self) -> bool {
+        self.wanted_by.is_empty()
+            && self.required_by.is_empty()
+            && self.also.is_empty()
+            && self.alias.is_empty()
+    }
+
+    /// ## 指向该Unit的符号链接的内容，内置Unit没有文件，使用其名称
+    fn link_target(&self) -> String {
+        if self.fragment_path.is_empty() {
+            return self.name.clone();
+        }
+        self.fragment_path.clone()
+    }
+
+    /// ## 启用该Unit需要在配置目录中创建的符号链接
+    fn links(&self) -> Vec<String> {
+        let mut links = Vec::new();
+        for target in self.wanted_by.iter() {
+            links.push(format!("{}.wants/{}", target, self.name));
+        }
+        for target in self.required_by.iter() {
+            links.push(format!("{}.requires/{}", target, self.name));
+        }
+        links.extend(self.alias.iter().cloned());
+        links
+    }
+}
+
+/// 预设策略对Unit的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PresetAction {
+    Enable,
+    Disable,
+    // 不修改Unit的启用状态
+    Ignore,
+}
+
+/// 预设策略中的一条规则，如enable foo*.service
+struct PresetRule {
+    action: PresetAction,
+    pattern: CString,
+}
+
+impl PresetRule {
+    fn matches(&self, name: &str) -> bool {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        unsafe { libc::fnmatch(self.pattern.as_ptr(), name.as_ptr(), 0) == 0 }
+    }
+}
+
+/// preset应用预设策略时允许的修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresetMode {
+    // 按照预设策略启用或禁用
+    #[default]
+    Full,
+    // 只启用Unit
+    EnableOnly,
+    // 只禁用Unit
+    DisableOnly,
+}
+
+impl PresetMode {
+    pub fn parse(s: &str) -> Result<PresetMode, RuntimeError> {
+        match s {
+            "full" => Ok(PresetMode::Full),
+            "enable-only" => Ok(PresetMode::EnableOnly),
+            "disable-only" => Ok(PresetMode::DisableOnly),
+            _ => {
+                eprintln!("Unknown preset mode: {}", s);
+                Err(RuntimeError::new(RuntimeErrorType::InvalidParameter))
+            }
+        }
+    }
+}
+
+pub struct InstallManager;
+
+impl InstallManager {
+    /// ## 按照[Install]段启用Unit
+    ///
+    /// 在WantedBy=、RequiredBy=的Unit的.wants/、.requires/目录中创建指向Unit文件的符号链接，
+    /// 为Alias=创建别名链接，并启用Also=中的Unit。runtime为true时只在运行时目录中启用
+    pub fn enable(
+        names: &[String],
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        let mut visited = Vec::new();
+        let mut pending: Vec<String> = names.to_vec();
+        while let Some(name) = pending.pop() {
+            let info = Self::install_info(&name)?;
+            if visited.contains(&info.name) {
+                continue;
+            }
+            visited.push(info.name.clone());
+            if info.is_static() {
+                eprintln!(
+                    "The unit {} has no installation config (WantedBy=, RequiredBy=, Also=, Alias=), it is not meant to be enabled.",
+                    info.name
+                );
+                continue;
+            }
+            for link in info.links() {
+                changes.symlink(&format!("{}{}", dir, link), &info.link_target())?;
+            }
+            pending.extend(info.also.iter().cloned());
+        }
+        Ok(())
+    }
+
+    /// ## 删除启用Unit时创建的符号链接，并禁用Also=中的Unit
+    pub fn disable(
+        names: &[String],
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        let mut visited = Vec::new();
+        let mut pending: Vec<String> = names.to_vec();
+        while let Some(name) = pending.pop() {
+            let info = Self::install_info(&name)?;
+            if visited.contains(&info.name) {
+                continue;
+            }
+            visited.push(info.name.clone());
+            for link in info.links() {
+                changes.unlink(&format!("{}{}", dir, link))?;
+            }
+            // 删除[Install]段修改之前创建的链接
+            for link in Self::find_links(dir, &info.name) {
+                changes.unlink(&link)?;
+            }
+            pending.extend(info.also.iter().cloned());
+        }
+        Ok(())
+    }
+
+    /// ## 获取Unit的启用状态
+    pub fn unit_file_state(name: &str) -> Result<UnitFileState, RuntimeError> {
+        if UnitLookup::is_masked(name) {
+            return Ok(UnitFileState::Masked);
+        }
+        if UnitLookup::resolve_alias(name).is_some() {
+            return Ok(UnitFileState::Alias);
+        }
+        let info = Self::install_info(name)?;
+        let enabled_in = |dir: &str| {
+            info.links()
+                .iter()
+                .any(|link| fs::symlink_metadata(format!("{}{}", dir, link)).is_ok())
+        };
+        if UNIT_SEARCH_PATH
+            .iter()
+            .filter(|dir| **dir != RUNTIME_UNIT_DIR)
+            .any(|dir| enabled_in(dir))
+        {
+            return Ok(UnitFileState::Enabled);
+        }
+        if enabled_in(RUNTIME_UNIT_DIR) {
+            return Ok(UnitFileState::EnabledRuntime);
+        }
+        if info.is_static() {
+            return Ok(UnitFileState::Static);
+        }
+        if info.wanted_by.is_empty() && info.required_by.is_empty() && info.alias.is_empty() {
+            return Ok(UnitFileState::Indirect);
+        }
+        Ok(UnitFileState::Disabled)
+    }
+
+    /// ## 屏蔽Unit，在配置目录中创建指向/dev/null的同名链接
+    ///
+    /// 被屏蔽的Unit不能被手动启动，也不能作为依赖被启动。runtime为true时只屏蔽到重启为止
+    pub fn mask(
+        names: &[String],
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        for name in names {
+            if !UnitParseUtil::is_valid_unit_name(name) {
+                eprintln!("Invalid unit name {}", name);
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+            }
+            changes.symlink(&format!("{}{}", dir, name), MASK_TARGET)?;
+        }
+        Ok(())
+    }
+
+    /// ## 删除配置目录中屏蔽Unit的链接
+    pub fn unmask(
+        names: &[String],
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        for name in names {
+            let path = format!("{}{}", dir, name);
+            if fs::read_link(&path).map_or(false, |target| target == Path::new(MASK_TARGET)) {
+                changes.unlink(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// ## 将搜索路径之外的Unit文件链接到配置目录中，使其可以被加载
+    pub fn link(
+        paths: &[String],
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        for path in paths {
+            let name = Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            if !path.starts_with('/') || !UnitParseUtil::is_valid_file(path) {
+                eprintln!(
+                    "Unit file {} does not exist or is not an absolute path",
+                    path
+                );
+                return Err(RuntimeError::new(RuntimeErrorType::FileNotFound));
+            }
+            if !UnitParseUtil::is_valid_unit_name(name) {
+                eprintln!("Invalid unit name {}", name);
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+            }
+            let in_search_path = UNIT_SEARCH_PATH
+                .iter()
+                .any(|dir| Path::new(path).parent() == Some(Path::new(dir)));
+            if in_search_path {
+                eprintln!("Unit file {} is already in the search path", path);
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+            }
+            changes.symlink(&format!("{}{}", dir, name), path)?;
+        }
+        Ok(())
+    }
+
+    /// ## 在目标Unit的.wants/或.requires/目录中为Unit创建符号链接
+    ///
+    /// ### param suffix wants或requires
+    pub fn add_dependency(
+        target: &str,
+        names: &[String],
+        suffix: &str,
+        runtime: bool,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let dir = Self::config_dir(runtime);
+        let target = Self::install_info(target)?;
+        for name in names {
+            let info = Self::install_info(name)?;
+            let path = format!("{}{}.{}/{}", dir, target.name, suffix, info.name);
+            changes.symlink(&path, &info.link_target())?;
+        }
+        Ok(())
+    }
+
+    /// ## 设置默认启动的目标，替换原有的default.target链接
+    pub fn set_default(name: &str, changes: &mut InstallChanges) -> Result<(), RuntimeError> {
+        if UnitParseUtil::parse_type(name) != UnitType::Target {
+            eprintln!("{} is not a target unit", name);
+            return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+        }
+        let info = Self::install_info(name)?;
+        let path = format!("{}{}", ADMIN_UNIT_DIR, DEFAULT_TARGET);
+        let target = info.link_target();
+        if fs::read_link(&path).map_or(false, |old| old == Path::new(&target)) {
+            return Ok(());
+        }
+        changes.unlink(&path)?;
+        changes.symlink(&path, &target)
+    }
+
+    /// ## 获取默认启动的目标
+    ///
+    /// default.target为符号链接时为其指向的Unit，不存在时为multi-user.target
+    pub fn default_target() -> String {
+        if let Some(name) = UnitLookup::link_target(DEFAULT_TARGET) {
+            return name;
+        }
+        if !UnitParser::unit_exists(DEFAULT_TARGET) {
+            return FALLBACK_TARGET.to_string();
+        }
+        DEFAULT_TARGET.to_string()
+    }
+
+    /// ## 按照预设策略启用或禁用Unit
+    ///
+    /// 没有[Install]段的Unit不受影响
+    pub fn preset(
+        names: &[String],
+        mode: PresetMode,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let rules = Self::preset_rules();
+        for name in names {
+            let info = Self::install_info(name)?;
+            if info.is_static() {
+                continue;
+            }
+            Self::apply_preset(&rules, &info.name, mode, changes)?;
+        }
+        Ok(())
+    }
+
+    /// ## 按照预设策略启用或禁用搜索路径中的所有Unit
+    ///
+    /// 跳过别名、被屏蔽、没有[Install]段及无法加载的Unit
+    pub fn preset_all(mode: PresetMode, changes: &mut InstallChanges) -> Result<(), RuntimeError> {
+        let rules = Self::preset_rules();
+        for name in UnitLookup::list_unit_files() {
+            if !UnitParseUtil::is_valid_unit_name(&name)
+                || UnitLookup::resolve_alias(&name).is_some()
+                || UnitLookup::is_masked(&name)
+            {
+                continue;
+            }
+            let info = match Self::install_info(&name) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if info.is_static() {
+                continue;
+            }
+            Self::apply_preset(&rules, &info.name, mode, changes)?;
+        }
+        Ok(())
+    }
+
+    fn apply_preset(
+        rules: &[PresetRule],
+        name: &str,
+        mode: PresetMode,
+        changes: &mut InstallChanges,
+    ) -> Result<(), RuntimeError> {
+        let names = [name.to_string()];
+        match Self::preset_action(rules, name, mode) {
+            PresetAction::Enable => Self::enable(&names, false, changes),
+            PresetAction::Disable => Self::disable(&names, false, changes),
+            PresetAction::Ignore => Ok(()),
+        }
+    }
+
+    /// ## 得到预设策略对Unit的动作
+    ///
+    /// 第一条匹配的规则生效，没有匹配的规则时启用，mode不允许的动作视为Ignore
+    fn preset_action(rules: &[PresetRule], name: &str, mode: PresetMode) -> PresetAction {
+        let action = rules
+            .iter()
+            .find(|rule| rule.matches(name))
+            .map_or(PresetAction::Enable, |rule| rule.action);
+        match (action, mode) {
+            (PresetAction::Enable, PresetMode::DisableOnly)
+            | (PresetAction::Disable, PresetMode::EnableOnly) => PresetAction::Ignore,
+            _ => action,
+        }
+    }
+
+    /// ## 读取所有预设策略文件中的规则
+    fn preset_rules() -> Vec<PresetRule> {
+        Self::preset_rules_in(PRESET_DIRS)
+    }
+
+    /// ## 读取dirs中预设策略文件的规则
+    ///
+    /// 同名文件只读取靠前目录中的，策略文件按文件名排序，规则按照出现的顺序排列
+    fn preset_rules_in(dirs: &[&str]) -> Vec<PresetRule> {
+        let mut files: Vec<(String, String)> = Vec::new();
+        for dir in dirs {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.ends_with(".preset") && !files.iter().any(|(file, _)| *file == name) {
+                    files.push((name.clone(), format!("{}{}", dir, name)));
+                }
+            }
+        }
+        files.sort();
+
+        let mut rules = Vec::new();
+        for (_, path) in files {
+            let content = fs::read_to_string(&path).unwrap_or_default();
+            rules.extend(Self::parse_preset(&path, &content));
+        }
+        rules
+    }
+
+    /// ## 解析一个预设策略文件的内容
+    ///
+    /// 忽略空行及注释，无法解析的行输出错误后跳过
+    fn parse_preset(path: &str, content: &str) -> Vec<PresetRule> {
+        let mut rules = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let (action, pattern) = match line.split_once(char::is_whitespace) {
+                Some((action, pattern)) => (action, pattern.trim()),
+                None => (line, ""),
+            };
+            let action = match action {
+                "enable" => PresetAction::Enable,
+                "disable" => PresetAction::Disable,
+                "ignore" => PresetAction::Ignore,
+                _ => {
+                    eprintln!("{}:{}: unknown preset action: {}", path, i + 1, line);
+                    continue;
+                }
+            };
+            match CString::new(pattern) {
+                Ok(pattern) if !pattern.as_bytes().is_empty() => {
+                    rules.push(PresetRule { action, pattern })
+                }
+                _ => eprintln!("{}:{}: invalid preset rule: {}", path, i + 1, line),
+            }
+        }
+        rules
+    }
+
+    /// 存放启用链接的目录
+    fn config_dir(runtime: bool) -> &'static str {
+        if runtime {
+            RUNTIME_UNIT_DIR
+        } else {
+            ADMIN_UNIT_DIR
+        }
+    }
+
+    /// ## 查找目录中.wants/、.requires/下指向该Unit的链接、该Unit的别名链接及link创建的链接
+    fn find_links(dir: &str, name: &str) -> Vec<String> {
+        let mut links = Vec::new();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return links,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let path = format!("{}{}", dir, file_name);
+            if file_name.ends_with(".wants") || file_name.ends_with(".requires") {
+                let link = format!("{}/{}", path, name);
+                if fs::symlink_metadata(&link).is_ok() {
+                    links.push(link);
+                }
+            } else if file_name == name {
+                // link创建的指向搜索路径之外的Unit文件的链接
+                let linked =
+                    fs::read_link(&path).map_or(false, |target| target != Path::new(MASK_TARGET));
+                if linked {
+                    links.push(path);
+                }
+            } else {
+                let target = fs::read_link(&path).ok();
+                let target_name = target
+                    .as_ref()
+                    .and_then(|target| target.file_name())
+                    .and_then(|target| target.to_str());
+                if target_name == Some(name) {
+                    links.push(path);
+                }
+            }
+        }
+        links
+    }
+
+    /// ## 加载Unit并获取其[Install]段
+    ///
+    /// 无法加载时移除本次加载创建的占位Unit，查询或启用失败的Unit不会被留在Unit表中
+    fn install_info(name: &str) -> Result<InstallInfo, RuntimeError> {
+        let loaded = UnitManager::contains_name(name);
+        let id = match UnitParseUtil::load_unit(name) {
+            Ok(id) => id,
+            Err(_) => {
+                eprintln!("Invalid unit name {}", name);
+                return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter));
+            }
+        };
+        let unit = UnitManager::get_unit_with_id(&id).unwrap();
+        let guard = unit.lock().unwrap();
+        let base = guard.unit_base();
+        let error = match base.load_state() {
+            LoadState::NotFound => {
+                eprintln!("Unit file {} does not exist.", name);
+                Some(RuntimeErrorType::FileNotFound)
+            }
+            LoadState::Error => {
+                eprintln!("Unit {} failed to load: {}", name, base.load_error());
+                Some(RuntimeErrorType::InvalidFileFormat)
+            }
+            LoadState::Masked => {
+                eprintln!("Unit file {} is masked.", name);
+                Some(RuntimeErrorType::UnsupportedOperation)
+            }
+            LoadState::Loaded => None,
+        };
+        if let Some(error) = error {
+            drop(guard);
+            if !loaded {
+                UnitParser::remove_placeholder(name, id);
+            }
+            return Err(RuntimeError::new(error));
+        }
+        let unit_name = base.unit_name();
+        let fragment_path = base.fragment_path().to_string();
+        let install = base.install_part().clone();
+        drop(guard);
+
+        // 释放当前Unit的锁之后再获取其他Unit的名称
+        let names = |ids: &[usize]| -> Vec<String> {
+            ids.iter()
+                .filter_map(|id| UnitManager::get_unit_with_id(id))
+                .map(|unit| unit.lock().unwrap().unit_base().unit_name())
+                .collect()
+        };
+        Ok(InstallInfo {
+            name: unit_name,
+            fragment_path,
+            wanted_by: names(install.wanted_by()),
+            required_by: names(install.requires_by()),
+            also: names(install.also()),
+            alias: install.alias().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(rules: &[PresetRule], name: &str) -> PresetAction {
+        InstallManager::preset_action(rules, name, PresetMode::Full)
+    }
+
+    #[test]
+    fn parse_preset_rules() {
+        let content = "\
+# comment
+; another comment
+
+enable  foo.service
+disable *
+bogus bar.service
+ignore
+";
+        let rules = InstallManager::parse_preset("test.preset", content);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].action, PresetAction::Enable);
+        assert_eq!(rules[0].pattern.to_str().unwrap(), "foo.service");
+        assert_eq!(rules[1].action, PresetAction::Disable);
+        assert_eq!(rules[1].pattern.to_str().unwrap(), "*");
+    }
+
+    #[test]
+    fn preset_first_match_wins() {
+        let content = "\
+disable foo-debug.service
+enable foo*.service
+ignore bar.service
+disable *.timer
+";
+        let rules = InstallManager::parse_preset("test.preset", content);
+        assert_eq!(action(&rules, "foo-debug.service"), PresetAction::Disable);
+        assert_eq!(action(&rules, "foo.service"), PresetAction::Enable);
+        assert_eq!(action(&rules, "bar.service"), PresetAction::Ignore);
+        assert_eq!(action(&rules, "clean.timer"), PresetAction::Disable);
+        // 没有匹配的规则时启用
+        assert_eq!(action(&rules, "baz.service"), PresetAction::Enable);
+        assert_eq!(action(&[], "baz.service"), PresetAction::Enable);
+    }
+
+    #[test]
+    fn preset_mode_limits_actions() {
+        let rules = InstallManager::parse_preset("test.preset", "disable foo.service\n");
+        let preset = |name, mode| InstallManager::preset_action(&rules, name, mode);
+        assert_eq!(
+            preset("foo.service", PresetMode::EnableOnly),
+            PresetAction::Ignore
+        );
+        assert_eq!(
+            preset("foo.service", PresetMode::DisableOnly),
+            PresetAction::Disable
+        );
+        assert_eq!(
+            preset("bar.service", PresetMode::EnableOnly),
+            PresetAction::Enable
+        );
+        assert_eq!(
+            preset("bar.service", PresetMode::DisableOnly),
+            PresetAction::Ignore
+        );
+    }
+
+    #[test]
+    fn preset_files_shadow_by_name() {
+        let root = std::env::temp_dir().join(format!("reach-preset-test-{}", std::process::id()));
+        let high = format!("{}/etc/", root.display());
+        let low = format!("{}/lib/", root.display());
+        fs::create_dir_all(&high).unwrap();
+        fs::create_dir_all(&low).unwrap();
+        // 靠前目录中的同名文件覆盖靠后目录中的文件
+        fs::write(format!("{}50-vendor.preset", high), "disable foo.service\n").unwrap();
+        fs::write(format!("{}50-vendor.preset", low), "enable foo.service\n").unwrap();
+        // 不同名的文件按文件名排序，排在前面的文件中的规则先匹配
+        fs::write(format!("{}10-local.preset", low), "enable bar.service\n").unwrap();
+        fs::write(format!("{}90-default.preset", low), "disable *\n").unwrap();
+        fs::write(format!("{}foo.conf", low), "enable *\n").unwrap();
+
+        let rules = InstallManager::preset_rules_in(&[high.as_str(), low.as_str()]);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(action(&rules, "foo.service"), PresetAction::Disable);
+        assert_eq!(action(&rules, "bar.service"), PresetAction::Enable);
+        assert_eq!(action(&rules, "baz.service"), PresetAction::Disable);
+    }
+
+    #[test]
+    fn missing_unit_leaves_no_placeholder() {
+        let name = "install-test-missing.service";
+        assert!(InstallManager::install_info(name).is_err());
+        assert!(!UnitManager::contains_name(name));
+    }
+
+    #[test]
+    fn referenced_placeholder_is_kept() {
+        // 已被其他Unit引用的占位Unit不受查询的影响
+        let name = "install-test-referenced.service";
+        let id = UnitParser::placeholder(name, LoadState::NotFound, String::new());
+        assert!(InstallManager::install_info(name).is_err());
+        assert_eq!(UnitManager::get_id_with_path(name), Some(id));
+        assert!(UnitManager::contains_id(&id));
+    }
+}
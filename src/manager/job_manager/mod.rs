@@ -0,0 +1,822 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+
+use crate::{
+    error::{
+        runtime_error::{RuntimeError, RuntimeErrorType},
+        ErrorFormat,
+    },
+    executor::{
+        dep_graph::{DepGraph, DepType},
+        Executor,
+    },
+    unit::{LoadState, UnitState},
+};
+
+use super::{
+    manager_config::{LogLevel, ManagerConfig},
+    shutdown_manager::ShutdownManager,
+    UnitManager,
+};
+
+lazy_static! {
+    /// 已安装的作业，Unit id到其作业的映射，每个Unit同时最多只有一个作业
+    static ref JOB_TABLE: RwLock<HashMap<usize, Job>> = RwLock::new(HashMap::new());
+}
+
+fn generate_job_id() -> usize {
+    static JOB_ID: AtomicUsize = AtomicUsize::new(1);
+    JOB_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+/// 作业类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobType {
+    // 启动Unit
+    Start,
+    // 停止Unit
+    Stop,
+    // 先停止再启动Unit
+    Restart,
+    // 重新加载Unit
+    Reload,
+    // 检查Unit是否处于运行状态，不会启动Unit，用于Requisite=
+    VerifyActive,
+}
+
+impl JobType {
+    /// ## 合并同一Unit上的两个作业，无法合并时返回None
+    fn merge(a: JobType, b: JobType) -> Option<JobType> {
+        use JobType::*;
+        match (a, b) {
+            _ if a == b => Some(a),
+            (Stop, _) | (_, Stop) => None,
+            (Restart, _) | (_, Restart) => Some(Restart),
+            (Start, Reload) | (Reload, Start) => Some(Restart),
+            (Start, VerifyActive) | (VerifyActive, Start) => Some(Start),
+            (Reload, VerifyActive) | (VerifyActive, Reload) => Some(Reload),
+            _ => None,
+        }
+    }
+
+    fn is_stop(&self) -> bool {
+        *self == JobType::Stop
+    }
+}
+
+impl fmt::Display for JobType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            JobType::Start => "start",
+            JobType::Stop => "stop",
+            JobType::Restart => "restart",
+            JobType::Reload => "reload",
+            JobType::VerifyActive => "verify-active",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 作业模式，对应--job-mode=
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JobMode {
+    // 替换与新作业冲突的已有作业
+    #[default]
+    Replace,
+    // 新作业与已有作业冲突时失败
+    Fail,
+    // 与replace相同，并停止其余所有运行中的Unit
+    Isolate,
+    // 忽略所有依赖，包括排序依赖
+    IgnoreDependencies,
+    // 忽略Requires=、Wants=等依赖，但仍遵循排序依赖
+    IgnoreRequirements,
+}
+
+impl JobMode {
+    pub fn parse(s: &str) -> Result<JobMode, RuntimeError> {
+        let mode = match s {
+            "replace" => JobMode::Replace,
+            "fail" => JobMode::Fail,
+            "isolate" => JobMode::Isolate,
+            "ignore-dependencies" => JobMode::IgnoreDependencies,
+            "ignore-requirements" => JobMode::IgnoreRequirements,
+            _ => return Err(RuntimeError::new(RuntimeErrorType::InvalidParameter)),
+        };
+        Ok(mode)
+    }
+}
+
+/// 作业状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    // 等待排序在其之前的作业完成
+    Waiting,
+    // 已开始执行，等待Unit到达目标状态
+    Running,
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            JobState::Waiting => "waiting",
+            JobState::Running => "running",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 作业结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobResult {
+    Done,
+    Failed,
+    Canceled,
+    // 所依赖的作业失败
+    Dependency,
+}
+
+impl fmt::Display for JobResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            JobResult::Done => "done",
+            JobResult::Failed => "failed",
+            JobResult::Canceled => "canceled",
+            JobResult::Dependency => "dependency",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 已安装的作业
+#[derive(Debug, Clone)]
+pub struct Job {
+    id: usize,
+    unit: usize,
+    job_type: JobType,
+    state: JobState,
+    // Restart作业当前处于停止阶段
+    stopping: bool,
+    // 这些Unit的作业失败时本作业也失败(Requires=、BindsTo=、Requisite=)
+    requires: Vec<usize>,
+    // 安装时记录的排序依赖，执行期间不再读取Unit
+    after: Vec<usize>,
+    before: Vec<usize>,
+    // 忽略排序依赖(--job-mode=ignore-dependencies)
+    ignore_order: bool,
+}
+
+#[allow(dead_code)]
+impl Job {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn unit(&self) -> usize {
+        self.unit
+    }
+
+    pub fn job_type(&self) -> JobType {
+        self.job_type
+    }
+
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    /// ## 判断本作业是否需要等待另一个作业完成
+    ///
+    /// 存在排序依赖时，启动类作业按照After=顺序执行，停止作业按照相反顺序执行，
+    /// 同时存在停止与启动作业时停止作业先执行
+    fn waits_for(&self, other: &Job) -> bool {
+        if self.ignore_order || other.unit == self.unit {
+            return false;
+        }
+        let other_first = self.after.contains(&other.unit) || other.before.contains(&self.unit);
+        let self_first = self.before.contains(&other.unit) || other.after.contains(&self.unit);
+        (other_first && !self.job_type.is_stop()) || (self_first && other.job_type.is_stop())
+    }
+}
+
+/// 事务中的作业
+#[derive(Debug, Clone)]
+struct TransactionJob {
+    job_type: JobType,
+    // 必须成功的作业，与其他作业冲突时事务失败；非必须的作业冲突时被丢弃
+    essential: bool,
+    requires: Vec<usize>,
+}
+
+/// ## 由一次请求生成的作业集合
+///
+/// 从锚定作业出发沿依赖关系展开，检查冲突及排序环后一次性安装
+struct Transaction {
+    mode: JobMode,
+    jobs: HashMap<usize, TransactionJob>,
+}
+
+impl Transaction {
+    fn new(mode: JobMode) -> Self {
+        Transaction {
+            mode,
+            jobs: HashMap::new(),
+        }
+    }
+
+    /// ## 向事务中添加作业并展开其依赖
+    fn add_job(
+        &mut self,
+        unit: usize,
+        job_type: JobType,
+        essential: bool,
+        anchor: bool,
+    ) -> Result<(), RuntimeError> {
+        if let Some(job) = self.jobs.get_mut(&unit) {
+            match JobType::merge(job.job_type, job_type) {
+                Some(merged) => {
+                    job.job_type = merged;
+                    job.essential |= essential;
+                    return Ok(());
+                }
+                None if !essential => {
+                    eprintln!(
+                        "{}: dropping {} job conflicting with {} job",
+                        Self::unit_name(unit),
+                        job_type,
+                        job.job_type
+                    );
+                    return Ok(());
+                }
+                None if !job.essential => {
+                    eprintln!(
+                        "{}: dropping {} job conflicting with {} job",
+                        Self::unit_name(unit),
+                        job.job_type,
+                        job_type
+                    );
+                    self.jobs.remove(&unit);
+                }
+                None => {
+                    eprintln!(
+                        "Transaction contains conflicting jobs '{}' and '{}' for {}",
+                        job.job_type,
+                        job_type,
+                        Self::unit_name(unit)
+                    );
+                    return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
+                }
+            }
+        }
+
+        if matches!(
+            job_type,
+            JobType::Start | JobType::Restart | JobType::Reload
+        ) {
+            Self::check_loaded(unit)?;
+        }
+
+        self.jobs.insert(
+            unit,
+            TransactionJob {
+                job_type,
+                essential,
+                requires: Vec::new(),
+            },
+        );
+
+        let ignore_requirements = matches!(
+            self.mode,
+            JobMode::IgnoreDependencies | JobMode::IgnoreRequirements
+        );
+        if anchor && ignore_requirements {
+            return Ok(());
+        }
+
+        let unit_arc = match UnitManager::get_unit_with_id(&unit) {
+            Some(unit) => unit,
+            None => return Err(RuntimeError::new(RuntimeErrorType::FileNotFound)),
+        };
+        let part = unit_arc.lock().unwrap().unit_base().unit_part().clone();
+
+        match job_type {
+            JobType::Start | JobType::Restart => {
+                let mut requires = Vec::new();
+                for dep in part.requires().iter().chain(part.binds_to()) {
+                    self.add_job(*dep, JobType::Start, essential, false)?;
+                    requires.push(*dep);
+                }
+                for dep in part.requisite() {
+                    self.add_job(*dep, JobType::VerifyActive, essential, false)?;
+                    requires.push(*dep);
+                }
+                // Wants=的依赖失败不影响当前Unit
+                for dep in part.wants() {
+                    if let Err(e) = self.add_job(*dep, JobType::Start, false, false) {
+                        eprintln!(
+                            "{}: ignoring wanted unit {}: {}",
+                            Self::unit_name(unit),
+                            Self::unit_name(*dep),
+                            e.error_format()
+                        );
+                    }
+                }
+                for dep in part.conflicts().iter().chain(part.conflicted_by()) {
+                    self.add_job(*dep, JobType::Stop, essential, false)?;
+                }
+                if let Some(job) = self.jobs.get_mut(&unit) {
+                    job.requires = requires;
+                }
+                // 重启时同时重启运行中的BindsTo=、PartOf=当前Unit的Unit
+                if job_type == JobType::Restart {
+                    let deps = part.be_binded_by().iter().chain(part.consists_of());
+                    for dep in deps.filter(|id| JobManager::is_active(**id)) {
+                        self.add_job(*dep, JobType::Restart, false, false)?;
+                    }
+                }
+            }
+            // 停止依赖当前Unit的Unit
+            JobType::Stop => {
+                let deps = part
+                    .required_by()
+                    .iter()
+                    .chain(part.be_binded_by())
+                    .chain(part.consists_of());
+                for dep in deps {
+                    self.add_job(*dep, JobType::Stop, essential, false)?;
+                }
+            }
+            JobType::Reload | JobType::VerifyActive => {}
+        }
+        Ok(())
+    }
+
+    /// ## isolate模式下停止所有不在事务中的运行中的Unit
+    fn add_isolate_jobs(&mut self) -> Result<(), RuntimeError> {
+        let running: Vec<usize> = UnitManager::get_all_units()
+            .into_iter()
+            .filter(|id| !self.jobs.contains_key(id) && JobManager::is_active(*id))
+            .collect();
+        for id in running {
+            self.add_job(id, JobType::Stop, false, false)?;
+        }
+        Ok(())
+    }
+
+    /// ## 检查事务中的作业是否存在排序环
+    ///
+    /// 环上存在由Wants=引入的非必须作业时，删除该作业以打破环，否则事务失败
+    fn break_order_cycles(&mut self) -> Result<(), RuntimeError> {
+        if self.mode == JobMode::IgnoreDependencies {
+            return Ok(());
+        }
+        // 已安装的作业同样参与排序，与其形成的环同样会使作业无法执行
+        let installed: Vec<usize> = JOB_TABLE.read().unwrap().keys().cloned().collect();
+        loop {
+            let mut units: Vec<usize> = self.jobs.keys().cloned().collect();
+            for unit in installed.iter() {
+                if !units.contains(unit) {
+                    units.push(*unit);
+                }
+            }
+            let graph = DepGraph::from_units(&units);
+            let cycle = match graph.find_cycle(DepType::ORDERING) {
+                Some(cycle) => cycle,
+                None => return Ok(()),
+            };
+            let path = DepGraph::format_cycle(&cycle);
+            let victim = cycle
+                .iter()
+                .find(|id| self.jobs.get(*id).map_or(false, |job| !job.essential));
+            match victim {
+                Some(victim) => {
+                    eprintln!(
+                        "Found ordering cycle: {}; breaking it by deleting job {}/{}",
+                        path,
+                        Self::unit_name(*victim),
+                        self.jobs[victim].job_type
+                    );
+                    self.drop_job(*victim);
+                }
+                None => {
+                    eprintln!("Found ordering cycle: {}; transaction is cyclic", path);
+                    return Err(RuntimeError::new(RuntimeErrorType::CircularDependency));
+                }
+            }
+        }
+    }
+
+    /// ## 删除事务中的作业，以及依赖该作业的非必须作业
+    fn drop_job(&mut self, unit: usize) {
+        if self.jobs.remove(&unit).is_none() {
+            return;
+        }
+        let dependents: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| !job.essential && job.requires.contains(&unit))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in dependents {
+            self.drop_job(id);
+        }
+    }
+
+    /// ## 检查Unit是否已成功加载，未找到或解析失败的Unit不能被启动
+    fn check_loaded(id: usize) -> Result<(), RuntimeError> {
+        let unit = match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => unit,
+            None => return Err(RuntimeError::new(RuntimeErrorType::FileNotFound)),
+        };
+        let unit = unit.lock().unwrap();
+        let base = unit.unit_base();
+        match base.load_state() {
+            LoadState::Loaded => Ok(()),
+            LoadState::NotFound => {
+                eprintln!("Unit {} not found.", base.unit_name());
+                Err(RuntimeError::new(RuntimeErrorType::FileNotFound))
+            }
+            LoadState::Error => {
+                eprintln!(
+                    "Unit {} failed to load: {}",
+                    base.unit_name(),
+                    base.load_error()
+                );
+                Err(RuntimeError::new(RuntimeErrorType::InvalidFileFormat))
+            }
+            LoadState::Masked => {
+                eprintln!("Unit {} is masked.", base.unit_name());
+                Err(RuntimeError::new(RuntimeErrorType::UnsupportedOperation))
+            }
+        }
+    }
+
+    fn unit_name(id: usize) -> String {
+        match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => unit.lock().unwrap().unit_base().unit_name(),
+            None => id.to_string(),
+        }
+    }
+}
+
+pub struct JobManager;
+
+#[allow(dead_code)]
+impl JobManager {
+    /// ## 为Unit提交一个作业
+    ///
+    /// 沿依赖关系生成事务，检查冲突与排序环后安装到作业表中，由主循环调度执行
+    ///
+    /// ### return 成功则返回锚定作业的id
+    pub fn add_job(unit: usize, job_type: JobType, mode: JobMode) -> Result<usize, RuntimeError> {
+        // 关机期间只接受停止作业
+        if ShutdownManager::is_shutting_down() && !job_type.is_stop() {
+            eprintln!(
+                "Shutdown in progress, refusing to {} {}",
+                job_type,
+                Transaction::unit_name(unit)
+            );
+            return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
+        }
+        let mut transaction = Transaction::new(mode);
+        transaction.add_job(unit, job_type, true, true)?;
+        if mode == JobMode::Isolate {
+            transaction.add_isolate_jobs()?;
+        }
+        transaction.break_order_cycles()?;
+        let ids = Self::install(transaction)?;
+        Ok(ids[&unit])
+    }
+
+    /// ## 将事务中的作业安装到作业表中
+    ///
+    /// ### return Unit id到其作业id的映射
+    fn install(transaction: Transaction) -> Result<HashMap<usize, usize>, RuntimeError> {
+        let ignore_order = transaction.mode == JobMode::IgnoreDependencies;
+        let mut table = JOB_TABLE.write().unwrap();
+
+        // fail模式下不允许替换已有的作业
+        if transaction.mode == JobMode::Fail {
+            for (unit, job) in transaction.jobs.iter() {
+                if let Some(installed) = table.get(unit) {
+                    if JobType::merge(installed.job_type, job.job_type).is_none() {
+                        eprintln!(
+                            "Transaction is destructive: {} has conflicting job {}",
+                            Transaction::unit_name(*unit),
+                            installed.id
+                        );
+                        return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
+                    }
+                }
+            }
+        }
+
+        let mut ids = HashMap::new();
+        for (unit, job) in transaction.jobs {
+            let merged = table.get(&unit).and_then(|installed| {
+                JobType::merge(installed.job_type, job.job_type)
+                    .filter(|t| installed.state == JobState::Waiting || *t == installed.job_type)
+            });
+            let id = match (table.get_mut(&unit), merged) {
+                // 与已有作业合并
+                (Some(installed), Some(job_type)) => {
+                    installed.job_type = job_type;
+                    for dep in job.requires {
+                        if !installed.requires.contains(&dep) {
+                            installed.requires.push(dep);
+                        }
+                    }
+                    installed.id
+                }
+                (installed, _) => {
+                    if let Some(installed) = installed {
+                        eprintln!(
+                            "Job {} {}/{} canceled",
+                            installed.id,
+                            Transaction::unit_name(unit),
+                            installed.job_type
+                        );
+                    }
+                    let (after, before) = Self::ordering(unit);
+                    let job = Job {
+                        id: generate_job_id(),
+                        unit,
+                        job_type: job.job_type,
+                        state: JobState::Waiting,
+                        stopping: false,
+                        requires: job.requires,
+                        after,
+                        before,
+                        ignore_order,
+                    };
+                    let id = job.id;
+                    table.insert(unit, job);
+                    id
+                }
+            };
+            ids.insert(unit, id);
+        }
+        Ok(ids)
+    }
+
+    /// ## 调度作业
+    ///
+    /// 检查运行中的作业是否完成，并执行所有排序依赖已满足的等待中作业，直到没有作业状态发生变化。
+    /// 在主循环每次被唤醒时调用
+    pub fn dispatch() {
+        loop {
+            let mut progress = false;
+
+            let jobs: Vec<Job> = JOB_TABLE.read().unwrap().values().cloned().collect();
+            for job in jobs.iter().filter(|job| job.state == JobState::Running) {
+                if let Some(result) = Self::check_running(job) {
+                    Self::finish(job, result);
+                    progress = true;
+                }
+            }
+
+            // 同一轮中所有可以执行的作业同时开始执行
+            let jobs: Vec<Job> = JOB_TABLE.read().unwrap().values().cloned().collect();
+            let runnable: Vec<&Job> = jobs
+                .iter()
+                .filter(|job| {
+                    job.state == JobState::Waiting && !jobs.iter().any(|other| job.waits_for(other))
+                })
+                .collect();
+            for job in runnable {
+                Self::run_job(job);
+                progress = true;
+            }
+
+            if !progress {
+                break;
+            }
+        }
+    }
+
+    /// ## 开始执行作业
+    fn run_job(job: &Job) {
+        let result = match job.job_type {
+            JobType::Start => {
+                if Self::is_active(job.unit) {
+                    Some(JobResult::Done)
+                } else if Executor::exec(job.unit).is_err() {
+                    Some(JobResult::Failed)
+                } else {
+                    None
+                }
+            }
+            JobType::Stop | JobType::Restart => {
+                Self::stop_unit(job.unit);
+                None
+            }
+            JobType::Reload => match UnitManager::get_unit_with_id(&job.unit) {
+                Some(unit) => match unit.lock().unwrap().reload() {
+                    Ok(_) => None,
+                    Err(_) => Some(JobResult::Failed),
+                },
+                None => Some(JobResult::Failed),
+            },
+            JobType::VerifyActive => {
+                if Self::is_active(job.unit) {
+                    Some(JobResult::Done)
+                } else {
+                    Some(JobResult::Failed)
+                }
+            }
+        };
+
+        match result {
+            Some(result) => Self::finish(job, result),
+            None => {
+                if let Some(installed) = JOB_TABLE.write().unwrap().get_mut(&job.unit) {
+                    if installed.id == job.id {
+                        installed.state = JobState::Running;
+                        installed.stopping = job.job_type == JobType::Restart;
+                    }
+                }
+            }
+        }
+    }
+
+    /// ## 停止Unit，同步停止的Unit直接设置为inactive
+    fn stop_unit(id: usize) {
+        let unit = match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => unit,
+            None => return,
+        };
+        let mut unit = unit.lock().unwrap();
+        unit.exit();
+        if *unit.unit_base().state() == UnitState::Active {
+            unit.unit_base_mut().set_state(UnitState::Inactive);
+        }
+    }
+
+    /// ## 检查运行中的作业是否完成
+    fn check_running(job: &Job) -> Option<JobResult> {
+        let state = match UnitManager::get_unit_with_id(&job.unit) {
+            Some(unit) => *unit.lock().unwrap().unit_base().state(),
+            None => return Some(JobResult::Failed),
+        };
+
+        if job.stopping {
+            if state == UnitState::Deactivating {
+                return None;
+            }
+            // Restart作业停止完成后转为启动
+            if let Some(installed) = JOB_TABLE.write().unwrap().get_mut(&job.unit) {
+                if installed.id == job.id {
+                    installed.state = JobState::Waiting;
+                    installed.stopping = false;
+                    installed.job_type = JobType::Start;
+                }
+            }
+            return None;
+        }
+
+        match (job.job_type, state) {
+            (JobType::Start | JobType::Restart, UnitState::Failed) => Some(JobResult::Failed),
+            (JobType::Start | JobType::Restart, UnitState::Active | UnitState::Inactive) => {
+                Some(JobResult::Done)
+            }
+            (JobType::Stop, UnitState::Inactive | UnitState::Failed) => Some(JobResult::Done),
+            (JobType::Reload, UnitState::Reloading) => None,
+            (JobType::Reload, _) => Some(JobResult::Done),
+            _ => None,
+        }
+    }
+
+    /// ## 结束作业
+    ///
+    /// 作业失败时，依赖该Unit的作业同样失败，并启动该Unit的OnFailure=
+    fn finish(job: &Job, result: JobResult) {
+        {
+            let mut table = JOB_TABLE.write().unwrap();
+            match table.get(&job.unit) {
+                Some(installed) if installed.id == job.id => {
+                    table.remove(&job.unit);
+                }
+                _ => return,
+            }
+        }
+
+        let name = Transaction::unit_name(job.unit);
+        match result {
+            JobResult::Done => {
+                if ManagerConfig::log_level() >= LogLevel::Debug {
+                    eprintln!(
+                        "Job {} {}/{} finished: {}",
+                        job.id, name, job.job_type, result
+                    );
+                }
+            }
+            _ => eprintln!(
+                "Job {} {}/{} finished: {}",
+                job.id, name, job.job_type, result
+            ),
+        }
+
+        if !matches!(result, JobResult::Failed | JobResult::Dependency) {
+            return;
+        }
+
+        let dependents: Vec<Job> = JOB_TABLE
+            .read()
+            .unwrap()
+            .values()
+            .filter(|other| other.requires.contains(&job.unit))
+            .cloned()
+            .collect();
+        for dependent in dependents {
+            Self::finish(&dependent, JobResult::Dependency);
+        }
+
+        if result == JobResult::Failed && job.job_type != JobType::Stop {
+            let on_failure = match UnitManager::get_unit_with_id(&job.unit) {
+                Some(unit) => Vec::from(unit.lock().unwrap().unit_base().unit_part().on_failure()),
+                None => Vec::new(),
+            };
+            for id in on_failure {
+                if let Err(e) = Self::add_job(id, JobType::Start, JobMode::Replace) {
+                    eprintln!(
+                        "{}: failed to start OnFailure unit: {}",
+                        name,
+                        e.error_format()
+                    );
+                }
+            }
+        }
+    }
+
+    /// ## 取消作业，正在执行的操作不会被中断
+    ///
+    /// ### return 作业不存在时返回false
+    pub fn cancel(id: usize) -> bool {
+        let job = JOB_TABLE
+            .read()
+            .unwrap()
+            .values()
+            .find(|job| job.id == id)
+            .cloned();
+        match job {
+            Some(job) => {
+                Self::finish(&job, JobResult::Canceled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ## 取消所有作业
+    pub fn cancel_all() {
+        let jobs: Vec<Job> = JOB_TABLE.read().unwrap().values().cloned().collect();
+        for job in jobs {
+            Self::finish(&job, JobResult::Canceled);
+        }
+    }
+
+    /// ## 获取所有已安装的作业，按id排序
+    pub fn jobs() -> Vec<Job> {
+        let mut jobs: Vec<Job> = JOB_TABLE.read().unwrap().values().cloned().collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// ## 获取Unit当前的作业
+    pub fn unit_job(unit: usize) -> Option<Job> {
+        JOB_TABLE.read().unwrap().get(&unit).cloned()
+    }
+
+    /// 判断Unit是否处于运行状态
+    pub fn is_active(id: usize) -> bool {
+        if UnitManager::is_running_unit(&id) {
+            return true;
+        }
+        match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => matches!(
+                unit.lock().unwrap().unit_base().state(),
+                UnitState::Active | UnitState::Reloading
+            ),
+            None => false,
+        }
+    }
+
+    /// 获取Unit的排序依赖(After=, Before=)
+    fn ordering(id: usize) -> (Vec<usize>, Vec<usize>) {
+        match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => {
+                let unit = unit.lock().unwrap();
+                let part = unit.unit_base().unit_part();
+                (Vec::from(part.after()), Vec::from(part.before()))
+            }
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::BufRead;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use nix::sys::resource::Resource;
+
+use crate::error::parse_error::{ParseError, ParseErrorType};
+use crate::error::ErrorFormat;
+use crate::parse::parse_util::UnitParseUtil;
+use crate::parse::{UnitParser, RLIMIT_TABLE};
+use crate::task::exec_context::ResourceLimit;
+use crate::unit::UnitType;
+use crate::DRAGON_REACH_CONF;
+
+lazy_static! {
+    /// 管理器全局配置，来自DRAGON_REACH_CONF的[Manager]段
+    static ref MANAGER_CONFIG: RwLock<ManagerConfig> = RwLock::new(ManagerConfig::default());
+}
+
+/// 日志等级，对应[Manager]段的LogLevel=
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    Emerg,
+    Alert,
+    Crit,
+    Err,
+    Warning,
+    Notice,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn parse(s: &str) -> Result<LogLevel, ParseError> {
+        let level = match s {
+            "emerg" | "0" => LogLevel::Emerg,
+            "alert" | "1" => LogLevel::Alert,
+            "crit" | "2" => LogLevel::Crit,
+            "err" | "3" => LogLevel::Err,
+            "warning" | "4" => LogLevel::Warning,
+            "notice" | "5" => LogLevel::Notice,
+            "info" | "6" => LogLevel::Info,
+            "debug" | "7" => LogLevel::Debug,
+            _ => return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0)),
+        };
+        Ok(level)
+    }
+}
+
+/// DragonReach管理器的全局配置
+#[derive(Debug, Clone, Default)]
+pub struct ManagerConfig {
+    // DefaultLimit*= 设置的资源限制，所有Unit未设置对应Limit*=时继承该值
+    default_limits: Vec<(Resource, ResourceLimit)>,
+    // LogLevel= 设置的日志等级，低于该等级的日志不输出
+    log_level: LogLevel,
+}
+
+impl ManagerConfig {
+    /// ## 加载管理器配置文件
+    ///
+    /// 配置文件不存在时使用默认配置，解析失败时打印错误并使用默认配置
+    pub fn load() {
+        if !UnitParseUtil::is_valid_file(DRAGON_REACH_CONF) {
+            return;
+        }
+        match Self::parse(DRAGON_REACH_CONF) {
+            Ok(config) => *MANAGER_CONFIG.write().unwrap() = config,
+            Err(e) => eprintln!("Err:{}", e.error_format()),
+        }
+    }
+
+    /// ## 解析管理器配置文件
+    pub fn parse(path: &str) -> Result<ManagerConfig, ParseError> {
+        let reader = UnitParser::get_reader(path, UnitType::Unknown)?;
+        let mut config = ManagerConfig::default();
+        let mut in_manager = false;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => {
+                    return Err(ParseError::new(
+                        ParseErrorType::EFILE,
+                        path.to_string(),
+                        i + 1,
+                    ));
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_manager = line == "[Manager]";
+                continue;
+            }
+            if !in_manager {
+                continue;
+            }
+
+            let (attr, val) = match line.find('=') {
+                Some(idx) => (line[..idx].trim(), line[idx + 1..].trim()),
+                None => {
+                    return Err(ParseError::new(
+                        ParseErrorType::ESyntaxError,
+                        path.to_string(),
+                        i + 1,
+                    ));
+                }
+            };
+
+            // 目前只支持LogLevel=与DefaultLimit*=，其余配置项忽略
+            let ret = if attr == "LogLevel" {
+                LogLevel::parse(val).map(|level| config.log_level = level)
+            } else {
+                let resource = match attr
+                    .strip_prefix("Default")
+                    .and_then(|name| RLIMIT_TABLE.get(name))
+                {
+                    Some(resource) => *resource,
+                    None => continue,
+                };
+                UnitParseUtil::parse_rlimit(val, resource)
+                    .map(|limit| config.set_default_limit(resource, limit))
+            };
+            if let Err(mut e) = ret {
+                e.set_file(path);
+                e.set_linenum(i + 1);
+                return Err(e);
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn set_default_limit(&mut self, resource: Resource, limit: ResourceLimit) {
+        match self.default_limits.iter_mut().find(|(r, _)| *r == resource) {
+            Some(item) => item.1 = limit,
+            None => self.default_limits.push((resource, limit)),
+        }
+    }
+
+    /// ## 获取管理器的默认资源限制
+    pub fn default_limits() -> Vec<(Resource, ResourceLimit)> {
+        MANAGER_CONFIG.read().unwrap().default_limits.clone()
+    }
+
+    /// ## 获取管理器的日志等级
+    pub fn log_level() -> LogLevel {
+        MANAGER_CONFIG.read().unwrap().log_level
+    }
+}
@@ -1,112 +1,172 @@
 pub mod ctl_manager;
+pub mod event_loop;
+pub mod install_manager;
+pub mod job_manager;
+pub mod manager_config;
+pub mod process_manager;
+pub mod reexec_manager;
+pub mod shutdown_manager;
 pub mod timer_manager;
 pub mod unit_manager;
 
 pub use unit_manager::*;
 
+use nix::unistd::Pid;
+
+use crate::error::ErrorFormat;
+use crate::executor::dep_graph::DepGraph;
 use crate::executor::ExitStatus;
+use crate::parse::lookup::UnitLookup;
+use crate::parse::parse_util::UnitParseUtil;
+use crate::parse::{UnitParser, UNIT_SUFFIX};
+use crate::unit::LoadState;
 
+use self::manager_config::{LogLevel, ManagerConfig};
+use self::process_manager::{ProcessExit, ProcessManager, ProcessOwner};
 use self::timer_manager::TimerManager;
-use crate::unit::signal::SIGCHILD_SIGNAL_RECEIVED;
-use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::Pid;
-use std::sync::atomic::Ordering;
 pub struct Manager;
 
 impl Manager {
-    /// ## 检查当前 DragonReach 运行的项目状态，并对其分发处理
+    /// ## 回收退出的子进程，并将其退出交给所属的 Unit 处理
+    ///
+    /// 在主循环收到 SIGCHLD 时调用
     pub fn check_running_status() {
-        if SIGCHILD_SIGNAL_RECEIVED
-            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
-            .is_ok()
-        {
-            let mut exited_unit: Vec<(usize, ExitStatus)> = Vec::new();
-            let mut running_manager = RUNNING_TABLE.write().unwrap();
-            // 检查所有运行中的 Unit
-            for unit in running_manager.mut_running_table() {
-                let pid = Pid::from_raw(unit.1.id() as i32);
-                // 检查 Unit 的运行状态
-                match waitpid(Some(pid), Some(WaitPidFlag::WNOHANG)) {
-                    // 若 Unit 为正常退出，则将其加入退出列表
-                    Ok(WaitStatus::Exited(_, status)) => {
-                        exited_unit.push((*unit.0, ExitStatus::from_exit_code(status)));
+        let mut exited_unit: Vec<(usize, ExitStatus)> = Vec::new();
+        let mut exited_control: Vec<(usize, Pid, ProcessExit)> = Vec::new();
+        // 回收所有退出的子进程，包括被托管给 DragonReach 的孤儿进程
+        for reaped in ProcessManager::reap() {
+            let pid = reaped.pid;
+            match reaped.owner {
+                // 只有当前运行表中的主进程退出才需要处理，显式停止的 Unit 已从运行表中移除
+                Some(ProcessOwner::Main(id)) => {
+                    if let ProcessExit::Killed(signal) | ProcessExit::Dumped(signal) = reaped.exit {
+                        eprintln!(
+                            "unit {} main process {} terminated by signal: {}",
+                            id, pid, signal
+                        );
                     }
-                    // 若 Unit 为被信号终止，则将其加入退出列表，并输出日志
-                    Ok(WaitStatus::Signaled(_, signal, _)) => {
-                        eprintln!("unit terminated by signal: {}", signal);
-                        exited_unit.push((*unit.0, ExitStatus::from_exit_code(!0)));
+                    if UnitManager::running_pid(id) == Some(pid) {
+                        exited_unit.push((id, ExitStatus::from_process_exit(reaped.exit)));
                     }
-                    // 其他错误情况
-                    Err(_) => {
-                        eprintln!("unit waitpid error");
+                }
+                // 控制进程的退出交给所属 Unit，由其执行下一个控制命令
+                Some(ProcessOwner::Control(id)) => exited_control.push((id, pid, reaped.exit)),
+                None => {
+                    if ManagerConfig::log_level() >= LogLevel::Debug {
+                        eprintln!("reaped unowned process {}: {:?}", pid, reaped.exit);
                     }
-                    // 若 Unit 正常运行，则不做处理
-                    Ok(_) => {}
                 }
             }
+        }
 
-            drop(running_manager);
-
-            // 处理退出的 Unit
-            for tmp in exited_unit {
-                // 将该任务从运行表中移除
-                UnitManager::remove_running(tmp.0);
-
-                // 取消该任务的定时器任务
-                TimerManager::cancel_timer(tmp.0);
+        // 处理退出的控制进程
+        for (id, pid, exit) in exited_control {
+            if let Some(unit) = UnitManager::get_unit_with_id(&id) {
+                unit.lock().unwrap().control_exit(pid, exit);
+            }
+        }
 
-                // 交付处理子进程退出逻辑
-                let _ = UnitManager::get_unit_with_id(&tmp.0)
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .exit();
+        // 处理退出的 Unit
+        for tmp in exited_unit {
+            // 将该任务从运行表中移除
+            UnitManager::remove_running(tmp.0);
 
-                // 更新属于该 Unit 的定时器任务
-                TimerManager::update_next_trigger(tmp.0, false);
+            // 更新属于该 Unit 的定时器任务
+            TimerManager::update_next_trigger(tmp.0, false);
 
-                // 交付处理子进程退出后逻辑
-                let unit = UnitManager::get_unit_with_id(&tmp.0).unwrap();
-                unit.lock().unwrap().after_exit(tmp.1);
-            }
-            // 若无运行中任务，则取出 IDLE 任务运行
-            if UnitManager::running_count() == 0 {
-                if let Some(unit) = UnitManager::pop_a_idle_service() {
-                    let _ = unit.lock().unwrap().run();
-                }
+            // 交付处理子进程退出后逻辑
+            let unit = UnitManager::get_unit_with_id(&tmp.0).unwrap();
+            unit.lock().unwrap().after_exit(tmp.1);
+        }
+        // 若无运行中任务，则取出 IDLE 任务运行
+        if UnitManager::running_count() == 0 {
+            if let Some(unit) = UnitManager::pop_a_idle_service() {
+                let _ = unit.lock().unwrap().run();
             }
         }
     }
 
-    /// ## 检查当前所有cmd进程的运行状态
-    pub fn check_cmd_proc() {
-        if SIGCHILD_SIGNAL_RECEIVED
-            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
-            .is_ok()
-        {
-            let mut exited = Vec::new();
-            let mut table = CMD_PROCESS_TABLE.write().unwrap();
+    /// ## 停止所有运行中的 Unit
+    pub fn stop_all_units() {
+        let ids: Vec<usize> = RUNNING_TABLE
+            .read()
+            .unwrap()
+            .running_table()
+            .keys()
+            .copied()
+            .collect();
+        for id in ids {
+            if let Some(unit) = UnitManager::get_unit_with_id(&id) {
+                unit.lock().unwrap().exit();
+            }
+        }
+    }
 
-            for tuple in table.iter_mut() {
-                let pid = Pid::from_raw(tuple.1.lock().unwrap().id() as i32);
-                match waitpid(Some(pid), Some(WaitPidFlag::WNOHANG)) {
-                    // 若 cmd 停止运行，则将其加入退出列表
-                    Ok(WaitStatus::Exited(_, _)) | Ok(WaitStatus::Signaled(_, _, _)) => {
-                        eprintln!("cmd exited");
-                        exited.push(*tuple.0);
-                    }
-                    Ok(_) => {}
-                    Err(_) => {
-                        // TODO: 应该添加错误处理，有一些命令执行失败会影响服务正常运行
-                        // 后续应该添加机制来执行服务相关命令启动失败的回调
-                        eprintln!("cmd waitpid error");
+    /// ## 重新读取所有Unit文件(daemon-reload)
+    ///
+    /// 已加载的Unit按照新的配置重新解析并继承原有的运行状态，正在运行的进程不受影响；
+    /// 文件已被删除的Unit标记为not found，被屏蔽的Unit标记为masked，解析失败的Unit保留原有配置并标记为error；
+    /// 搜索路径中新增的Unit文件将被加载，并输出其Unit名
+    pub fn reload_units() {
+        let mut ids = UnitManager::get_all_units();
+        ids.sort();
+        for id in ids {
+            let old = match UnitManager::get_unit_with_id(&id) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            let name = old.lock().unwrap().unit_base().unit_name();
+            // 运行中的Unit被屏蔽后继续运行，但不能再被启动
+            if UnitLookup::is_masked(&name) {
+                old.lock()
+                    .unwrap()
+                    .unit_base_mut()
+                    .set_load_state(LoadState::Masked);
+                continue;
+            }
+            if !UnitParser::unit_exists(&name) {
+                old.lock()
+                    .unwrap()
+                    .unit_base_mut()
+                    .set_load_state(LoadState::NotFound);
+                continue;
+            }
+            // 计时状态记录在计时器管理中的TimerUnit上，重新解析时会被替换，需先取出供新的Unit继承
+            let timer = TimerManager::get_timer_unit(id);
+            match UnitParser::reparse(&name) {
+                Ok(unit) => {
+                    match timer {
+                        Some(timer) => unit.lock().unwrap().reload_from(&*timer.lock().unwrap()),
+                        None => unit.lock().unwrap().reload_from(&*old.lock().unwrap()),
                     }
+                    UnitManager::replace_unit(id, unit);
+                    UnitParser::register_aliases(id);
+                }
+                Err(e) => {
+                    eprintln!("Failed to reload {}: {}", name, e.error_format());
+                    old.lock()
+                        .unwrap()
+                        .unit_base_mut()
+                        .set_load_state(LoadState::Error);
                 }
             }
+        }
 
-            for id in exited {
-                table.remove(&id);
+        // 加载新增的Unit文件
+        for name in UnitLookup::list_unit_files() {
+            let known = name
+                .rsplit_once('.')
+                .map_or(false, |(_, suffix)| UNIT_SUFFIX.contains_key(suffix));
+            if !known || UnitManager::contains_name(&name) {
+                continue;
+            }
+            match UnitParseUtil::parse_unit_no_type(&name) {
+                Ok(_) => println!("Loaded new unit {}", name),
+                Err(e) => eprintln!("Failed to load {}: {}", name, e.error_format()),
             }
         }
+
+        UnitManager::init_units_dependencies();
+        DepGraph::check_loaded_units();
     }
 }
@@ -0,0 +1,566 @@
+use std::{
+    fs, io,
+    process::{Child, Command},
+    sync::RwLock,
+    time::Duration,
+};
+
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{kill, killpg, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{getpid, Pid},
+};
+
+use crate::task::kill_context::{KillContext, KillPhase};
+
+use super::timer_manager::TimerManager;
+
+lazy_static! {
+    /// Unit id到其进程集合的映射
+    static ref UNIT_PROCESS_TABLE: RwLock<HashMap<usize, UnitProcesses>> = RwLock::new(HashMap::new());
+
+    /// pid到其所属Unit的索引，回收子进程时据此将退出状态交给对应的Unit
+    static ref PID_OWNER_TABLE: RwLock<HashMap<Pid, ProcessOwner>> = RwLock::new(HashMap::new());
+
+    /// 各Unit最近一次退出的主进程及控制进程
+    static ref UNIT_EXIT_TABLE: RwLock<HashMap<usize, UnitExit>> = RwLock::new(HashMap::new());
+}
+
+/// 进程的退出方式及状态，对应ExecMainCode=与ExecMainStatus=
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessExit {
+    /// 正常退出，记录退出码
+    Exited(i32),
+    /// 被信号终止
+    Killed(Signal),
+    /// 被信号终止并产生了core dump
+    Dumped(Signal),
+}
+
+impl ProcessExit {
+    /// 从waitpid的结果获取退出状态，进程未退出时返回None
+    pub fn from_wait_status(status: &WaitStatus) -> Option<ProcessExit> {
+        match *status {
+            WaitStatus::Exited(_, code) => Some(ProcessExit::Exited(code)),
+            WaitStatus::Signaled(_, signal, true) => Some(ProcessExit::Dumped(signal)),
+            WaitStatus::Signaled(_, signal, false) => Some(ProcessExit::Killed(signal)),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == ProcessExit::Exited(0)
+    }
+
+    /// 退出方式，与systemd的CLD_*名称一致
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProcessExit::Exited(_) => "exited",
+            ProcessExit::Killed(_) => "killed",
+            ProcessExit::Dumped(_) => "dumped",
+        }
+    }
+
+    /// 对应siginfo中的si_code，即CLD_EXITED、CLD_KILLED或CLD_DUMPED
+    pub fn si_code(&self) -> i32 {
+        match self {
+            ProcessExit::Exited(_) => libc::CLD_EXITED,
+            ProcessExit::Killed(_) => libc::CLD_KILLED,
+            ProcessExit::Dumped(_) => libc::CLD_DUMPED,
+        }
+    }
+
+    /// 退出码或终止进程的信号值
+    pub fn status(&self) -> i32 {
+        match *self {
+            ProcessExit::Exited(code) => code,
+            ProcessExit::Killed(signal) | ProcessExit::Dumped(signal) => signal as i32,
+        }
+    }
+}
+
+/// 一次被回收的子进程
+#[derive(Debug, Clone, Copy)]
+pub struct ReapedProcess {
+    pub pid: Pid,
+    pub exit: ProcessExit,
+    pub owner: Option<ProcessOwner>,
+}
+
+/// Unit最近一次退出的主进程及控制进程
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnitExit {
+    pub main: Option<(Pid, ProcessExit)>,
+    pub control: Option<(Pid, ProcessExit)>,
+}
+
+/// 进程的所属关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessOwner {
+    /// Unit的主进程
+    Main(usize),
+    /// Unit的控制进程(ExecStartPre=、ExecStartPost=等)
+    Control(usize),
+}
+
+impl ProcessOwner {
+    pub fn unit(&self) -> usize {
+        match self {
+            ProcessOwner::Main(id) | ProcessOwner::Control(id) => *id,
+        }
+    }
+}
+
+/// ## Unit的进程集合
+///
+/// Unit派生的每个进程(主进程及ExecStartPre等控制进程)都在子进程中调用setsid，
+/// 成为独立会话及进程组的首进程，其fork出的进程默认留在同一会话中。
+/// 因此无需cgroup，通过会话即可找到Unit的整个进程树
+#[derive(Debug, Clone, Default)]
+pub struct UnitProcesses {
+    main: Option<Pid>,
+    control: Vec<Pid>,
+    kill_context: KillContext,
+    // 停止时记录的各会话首进程的启动时间，进程已不存在时为None
+    start_times: Vec<(Pid, Option<u64>)>,
+}
+
+#[allow(dead_code)]
+impl UnitProcesses {
+    pub fn main(&self) -> Option<Pid> {
+        self.main
+    }
+
+    pub fn control(&self) -> &[Pid] {
+        &self.control
+    }
+
+    /// 该Unit所有进程所在的会话
+    fn sessions(&self) -> Vec<Pid> {
+        self.main
+            .iter()
+            .chain(self.control.iter())
+            .copied()
+            .collect()
+    }
+
+    /// ## 记录各会话首进程当前的启动时间
+    fn record_start_times(&mut self) {
+        self.start_times = self
+            .sessions()
+            .into_iter()
+            .map(|pid| (pid, ProcessManager::start_time(pid)))
+            .collect();
+    }
+
+    /// ## 丢弃pid已被其他进程复用的会话
+    ///
+    /// 延迟发送信号时会话首进程可能已被回收，其pid被其他进程(甚至是其他Unit的会话首进程)复用。
+    /// 已属于其他Unit，或启动时间与停止时记录的不同的pid不再属于该Unit
+    fn forget_reused(&mut self, id: usize) {
+        let owners = PID_OWNER_TABLE.read().unwrap();
+        let reused: Vec<Pid> = self
+            .sessions()
+            .into_iter()
+            .filter(|pid| {
+                let recorded = self
+                    .start_times
+                    .iter()
+                    .find(|(p, _)| p == pid)
+                    .and_then(|(_, time)| *time);
+                let foreign = owners.get(pid).map_or(false, |owner| owner.unit() != id);
+                foreign
+                    || ProcessManager::start_time(*pid).map_or(false, |now| recorded != Some(now))
+            })
+            .collect();
+        drop(owners);
+        if self.main.map_or(false, |main| reused.contains(&main)) {
+            self.main = None;
+        }
+        self.control.retain(|pid| !reused.contains(pid));
+    }
+
+    /// ## 按照KillMode=向进程发送该阶段对应的信号
+    fn kill(&self, phase: KillPhase) {
+        let (signal, all) = match self.kill_context.signal(phase) {
+            Some(ret) => ret,
+            None => return,
+        };
+
+        if !all {
+            if let Some(main) = self.main {
+                let _ = kill(main, signal);
+            }
+            return;
+        }
+
+        let sessions = self.sessions();
+        for pid in &sessions {
+            let _ = killpg(*pid, signal);
+        }
+        // 离开了原进程组但仍留在会话中的进程
+        for pid in ProcessManager::session_members(&sessions) {
+            let _ = kill(pid, signal);
+        }
+        // SIGKILL无法被捕获，其余信号需唤醒被停止的进程才能处理
+        if signal != Signal::SIGKILL {
+            for pid in &sessions {
+                let _ = killpg(*pid, Signal::SIGCONT);
+            }
+        }
+    }
+}
+
+pub struct ProcessManager;
+
+#[allow(dead_code)]
+impl ProcessManager {
+    /// ## 开始记录Unit的进程，在Unit启动前调用
+    ///
+    /// 丢弃该Unit上一次运行时记录的进程
+    pub fn watch_unit(id: usize, kill_context: KillContext) {
+        UNIT_PROCESS_TABLE.write().unwrap().insert(
+            id,
+            UnitProcesses {
+                kill_context,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// ## 创建Unit的进程并登记其所属关系
+    ///
+    /// 创建进程与登记期间持有pid索引的锁，保证主循环回收该进程时一定能找到其所属Unit
+    pub fn spawn(command: &mut Command, owner: ProcessOwner) -> io::Result<Child> {
+        let mut owners = PID_OWNER_TABLE.write().unwrap();
+        let child = command.spawn()?;
+        let pid = Pid::from_raw(child.id() as i32);
+        owners.insert(pid, owner);
+        drop(owners);
+
+        let mut table = UNIT_PROCESS_TABLE.write().unwrap();
+        let processes = table.entry(owner.unit()).or_default();
+        match owner {
+            ProcessOwner::Main(_) => processes.main = Some(pid),
+            _ => processes.control.push(pid),
+        }
+        Ok(child)
+    }
+
+    /// ## 接管daemon-reexec之前创建的Unit进程
+    ///
+    /// 重新执行后这些进程仍是DragonReach的子进程，但不再有对应的Child，只按pid登记其所属关系。
+    /// 进程已不存在时返回false，已退出但未被回收的进程仍会在回收时交给所属Unit
+    pub fn adopt(pid: Pid, owner: ProcessOwner) -> bool {
+        if kill(pid, None).is_err() {
+            return false;
+        }
+        PID_OWNER_TABLE.write().unwrap().insert(pid, owner);
+
+        let mut table = UNIT_PROCESS_TABLE.write().unwrap();
+        let processes = table.entry(owner.unit()).or_default();
+        match owner {
+            ProcessOwner::Main(_) => processes.main = Some(pid),
+            _ => processes.control.push(pid),
+        }
+        true
+    }
+
+    /// ## 回收所有已退出的子进程
+    ///
+    /// 以waitpid(-1)循环回收直到没有已退出的子进程，包括托管给DragonReach的孤儿进程，
+    /// 不会遗漏处理期间新退出的进程。回收的进程连同其所属关系返回给调用者分发
+    pub fn reap() -> Vec<ReapedProcess> {
+        let mut ret = Vec::new();
+        loop {
+            let mut owners = PID_OWNER_TABLE.write().unwrap();
+            let status = match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::StillAlive) => break,
+                Ok(status) => status,
+                Err(Errno::EINTR) => continue,
+                // ECHILD: 没有子进程
+                Err(_) => break,
+            };
+            let (pid, exit) = match (status.pid(), ProcessExit::from_wait_status(&status)) {
+                (Some(pid), Some(exit)) => (pid, exit),
+                _ => continue,
+            };
+            let owner = owners.remove(&pid);
+            drop(owners);
+            if let Some(owner) = owner {
+                Self::record_exit(owner, pid, exit);
+            }
+            ret.push(ReapedProcess { pid, exit, owner });
+        }
+        ret
+    }
+
+    fn record_exit(owner: ProcessOwner, pid: Pid, exit: ProcessExit) {
+        let mut table = UNIT_EXIT_TABLE.write().unwrap();
+        let record = table.entry(owner.unit()).or_default();
+        match owner {
+            ProcessOwner::Main(_) => record.main = Some((pid, exit)),
+            _ => record.control = Some((pid, exit)),
+        }
+    }
+
+    /// ## 获取Unit最近一次退出的主进程及控制进程
+    pub fn last_exit(id: usize) -> UnitExit {
+        UNIT_EXIT_TABLE
+            .read()
+            .unwrap()
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn unit_processes(id: usize) -> Option<UnitProcesses> {
+        UNIT_PROCESS_TABLE.read().unwrap().get(&id).cloned()
+    }
+
+    /// ## 停止Unit的所有进程
+    ///
+    /// 立即发送KillSignal=，若timeout后仍有进程未退出，则发送SIGKILL。timeout为0时立即发送SIGKILL
+    pub fn stop_unit(id: usize, timeout: Duration) {
+        let mut processes = match UNIT_PROCESS_TABLE.write().unwrap().remove(&id) {
+            Some(processes) => processes,
+            None => return,
+        };
+
+        if timeout.is_zero() {
+            processes.kill(KillPhase::Terminate);
+            processes.kill(KillPhase::Kill);
+            return;
+        }
+
+        processes.kill(KillPhase::Terminate);
+        processes.record_start_times();
+        // 定时器记录的是此次运行的进程，不会影响重启后的新进程
+        // 该定时器不属于Unit，避免被Unit退出时取消
+        TimerManager::push_timer(
+            timeout,
+            move || {
+                processes.forget_reused(id);
+                processes.kill(KillPhase::Kill);
+                Ok(())
+            },
+            0,
+        );
+    }
+
+    /// ## 立即终止Unit的所有进程
+    pub fn kill_unit(id: usize) {
+        Self::stop_unit(id, Duration::ZERO);
+    }
+
+    /// ## 查找属于给定会话的所有进程
+    ///
+    /// 通过/proc/[pid]/stat获取进程所在会话，/proc不可用时返回空
+    pub fn session_members(sessions: &[Pid]) -> Vec<Pid> {
+        Self::all_processes()
+            .into_iter()
+            .filter(|(_, _, session)| sessions.contains(session))
+            .map(|(pid, _, _)| pid)
+            .collect()
+    }
+
+    /// ## 查找DragonReach的所有后代进程
+    ///
+    /// DragonReach为子进程收割者，脱离Unit的孤儿进程同样是其后代
+    pub fn descendants() -> Vec<Pid> {
+        let processes = Self::all_processes();
+        let mut ret = vec![getpid()];
+        let mut i = 0;
+        while i < ret.len() {
+            let parent = ret[i];
+            ret.extend(
+                processes
+                    .iter()
+                    .filter(|(_, ppid, _)| *ppid == parent)
+                    .map(|(pid, _, _)| *pid),
+            );
+            i += 1;
+        }
+        ret.remove(0);
+        ret
+    }
+
+    /// ## 获取进程的启动时间，用于判断pid是否已被其他进程复用
+    ///
+    /// 进程不存在或/proc不可用时返回None
+    fn start_time(pid: Pid) -> Option<u64> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // 进程名之后的第20项为starttime
+        let idx = stat.rfind(')')?;
+        stat[idx + 1..].split_whitespace().nth(19)?.parse().ok()
+    }
+
+    /// 通过/proc获取所有进程的(pid, ppid, session)
+    fn all_processes() -> Vec<(Pid, Pid, Pid)> {
+        let mut ret = Vec::new();
+        let dir = match fs::read_dir("/proc") {
+            Ok(dir) => dir,
+            Err(_) => return ret,
+        };
+        for entry in dir.flatten() {
+            let pid = match entry
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<i32>().ok())
+            {
+                Some(pid) => pid,
+                None => continue,
+            };
+            let stat = match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                Ok(stat) => stat,
+                Err(_) => continue,
+            };
+            // 进程名可能包含空格，从最后一个')'之后开始解析: state ppid pgrp session
+            let fields: Vec<i32> = match stat.rfind(')') {
+                Some(idx) => stat[idx + 1..]
+                    .split_whitespace()
+                    .skip(1)
+                    .take(3)
+                    .filter_map(|s| s.parse::<i32>().ok())
+                    .collect(),
+                None => continue,
+            };
+            if fields.len() == 3 {
+                ret.push((
+                    Pid::from_raw(pid),
+                    Pid::from_raw(fields[0]),
+                    Pid::from_raw(fields[2]),
+                ));
+            }
+        }
+        ret
+    }
+}
+
+// 测试中的子进程统一由ProcessManager通过waitpid(-1)回收，而非Child::wait
+#[cfg(test)]
+#[allow(clippy::zombie_processes)]
+mod tests {
+    use super::*;
+    use std::{sync::Mutex, thread, time::Instant};
+
+    // waitpid(-1)会回收测试进程的所有子进程，相关测试需串行执行
+    static REAP_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    /// 反复回收直到回收了count个进程或超时
+    fn reap_until(count: usize) -> Vec<ReapedProcess> {
+        let mut ret = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while ret.len() < count && Instant::now() < deadline {
+            ret.extend(ProcessManager::reap());
+            thread::sleep(Duration::from_millis(1));
+        }
+        ret
+    }
+
+    #[test]
+    fn reap_many_short_lived_children() {
+        let _guard = REAP_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        const COUNT: usize = 128;
+
+        let mut expected = HashMap::new();
+        for i in 0..COUNT {
+            let owner = if i % 2 == 0 {
+                ProcessOwner::Main(1000 + i)
+            } else {
+                ProcessOwner::Control(1000 + i)
+            };
+            let child = ProcessManager::spawn(&mut shell(&format!("exit {}", i)), owner).unwrap();
+            expected.insert(Pid::from_raw(child.id() as i32), (owner, i as i32));
+        }
+
+        let reaped = reap_until(COUNT);
+        assert_eq!(reaped.len(), COUNT);
+        for process in reaped {
+            let (owner, code) = expected.remove(&process.pid).expect("unexpected pid");
+            assert_eq!(process.owner, Some(owner));
+            assert_eq!(process.exit, ProcessExit::Exited(code));
+        }
+        assert!(expected.is_empty());
+
+        let exit = ProcessManager::last_exit(1000).main.unwrap().1;
+        assert_eq!(exit, ProcessExit::Exited(0));
+        let exit = ProcessManager::last_exit(1001).control.unwrap().1;
+        assert_eq!(exit, ProcessExit::Exited(1));
+    }
+
+    #[test]
+    fn reap_records_signals_and_unowned_children() {
+        let _guard = REAP_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let killed =
+            ProcessManager::spawn(&mut shell("kill -KILL $$"), ProcessOwner::Main(2000)).unwrap();
+        let terminated =
+            ProcessManager::spawn(&mut shell("kill -TERM $$"), ProcessOwner::Control(2000))
+                .unwrap();
+        // 未通过ProcessManager创建的进程没有所属关系
+        let unowned = shell("exit 3").spawn().unwrap();
+
+        let reaped = reap_until(3);
+        assert_eq!(reaped.len(), 3);
+        for process in reaped {
+            let pid = process.pid.as_raw() as u32;
+            if pid == killed.id() {
+                assert_eq!(process.exit, ProcessExit::Killed(Signal::SIGKILL));
+                assert_eq!(process.exit.si_code(), libc::CLD_KILLED);
+                assert_eq!(process.exit.status(), libc::SIGKILL);
+            } else if pid == terminated.id() {
+                assert_eq!(process.exit, ProcessExit::Killed(Signal::SIGTERM));
+                assert_eq!(process.owner, Some(ProcessOwner::Control(2000)));
+            } else {
+                assert_eq!(pid, unowned.id());
+                assert_eq!(process.exit, ProcessExit::Exited(3));
+                assert_eq!(process.owner, None);
+            }
+        }
+    }
+
+    #[test]
+    fn delayed_kill_skips_reused_pids() {
+        let _guard = REAP_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let own = ProcessManager::spawn(&mut shell("sleep 10"), ProcessOwner::Main(3000)).unwrap();
+        // 被其他Unit复用的pid
+        let other =
+            ProcessManager::spawn(&mut shell("sleep 10"), ProcessOwner::Main(3001)).unwrap();
+        // 被同一Unit的新进程复用的pid，启动时间与记录的不同
+        let restarted =
+            ProcessManager::spawn(&mut shell("sleep 10"), ProcessOwner::Control(3000)).unwrap();
+        let [own, other, restarted] =
+            [&own, &other, &restarted].map(|child| Pid::from_raw(child.id() as i32));
+
+        let mut processes = UnitProcesses {
+            main: Some(own),
+            control: vec![other, restarted],
+            ..Default::default()
+        };
+        processes.record_start_times();
+        assert!(processes.start_times.iter().all(|(_, time)| time.is_some()));
+        processes.start_times[2].1 = processes.start_times[2].1.map(|time| time + 1);
+
+        processes.forget_reused(3000);
+        assert_eq!(processes.main(), Some(own));
+        assert!(processes.control().is_empty());
+
+        for pid in [own, other, restarted] {
+            let _ = kill(pid, Signal::SIGKILL);
+        }
+        assert_eq!(reap_until(3).len(), 3);
+    }
+}
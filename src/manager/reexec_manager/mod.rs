@@ -0,0 +1,177 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    os::fd::FromRawFd,
+};
+
+use nix::{
+    sys::memfd::{memfd_create, MemFdCreateFlag},
+    unistd::execv,
+};
+
+use crate::{
+    error::{
+        runtime_error::{RuntimeError, RuntimeErrorType},
+        ErrorFormat,
+    },
+    parse::parse_util::UnitParseUtil,
+};
+
+use super::{UnitManager, ID_TO_UNIT_MAP};
+
+/// 传递序列化状态所在文件描述符的命令行参数
+const DESERIALIZE_ARG: &str = "--deserialize";
+
+/// 一个Unit序列化后的运行状态
+pub struct SerializedUnit {
+    name: String,
+    items: Vec<(String, String)>,
+}
+
+pub struct ReexecManager;
+
+impl ReexecManager {
+    /// ## 重新执行DragonReach，用于不重启系统升级DragonReach
+    ///
+    /// 将各Unit的状态、主进程及控制进程、计时器的触发时间和重启次数序列化到memfd中，
+    /// 通过命令行参数将该文件描述符传给重新执行的DragonReach。进程号不变，运行中的进程仍是其子进程。
+    /// 尚未完成的作业不会被保留
+    ///
+    /// TODO: 目前尚不支持socket类型的Unit，没有需要传递的socket文件描述符
+    ///
+    /// ### return 只在重新执行失败时返回
+    pub fn reexec() -> Result<(), RuntimeError> {
+        let state = Self::serialize();
+        // 不设置MFD_CLOEXEC，使文件描述符在exec后保留
+        let name = CString::new("dragonreach-state").unwrap();
+        let fd = memfd_create(&name, MemFdCreateFlag::empty()).map_err(|e| {
+            eprintln!("Failed to create memfd: {}", e);
+            RuntimeError::new(RuntimeErrorType::ExecFailed)
+        })?;
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        if let Err(e) = file
+            .write_all(state.as_bytes())
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+        {
+            eprintln!("Failed to serialize state: {}", e);
+            return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
+        }
+
+        // 二进制文件被替换后/proc/self/exe指向已删除的旧文件，此时执行同一路径下的新文件
+        let exe = std::env::current_exe()
+            .map(|exe| {
+                exe.to_string_lossy()
+                    .trim_end_matches(" (deleted)")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| "/proc/self/exe".to_string());
+        let mut args: Vec<String> = Vec::new();
+        let mut old_args = std::env::args();
+        while let Some(arg) = old_args.next() {
+            if arg == DESERIALIZE_ARG {
+                old_args.next();
+                continue;
+            }
+            args.push(arg);
+        }
+        args.push(DESERIALIZE_ARG.to_string());
+        args.push(fd.to_string());
+
+        eprintln!("Reexecuting {}", exe);
+        let path = CString::new(exe).unwrap();
+        let args: Vec<CString> = args
+            .into_iter()
+            .map(|arg| CString::new(arg).unwrap())
+            .collect();
+        let err = execv(&path, &args).unwrap_err();
+        eprintln!("Failed to reexecute: {}", err);
+        Err(RuntimeError::new(RuntimeErrorType::ExecFailed))
+    }
+
+    /// ## 序列化所有Unit的运行状态
+    ///
+    /// 每个Unit以unit=<name>开始，随后每行一项key=value，以空行结束
+    fn serialize() -> String {
+        // 按照加载顺序序列化，恢复时被引用的Unit(如计时器的Unit=)先于引用者加载
+        let mut units: Vec<usize> = ID_TO_UNIT_MAP.read().unwrap().keys().copied().collect();
+        units.sort();
+        let mut ret = String::new();
+        for id in units {
+            let unit = match UnitManager::get_unit_with_id(&id) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            let unit = unit.lock().unwrap();
+            ret.push_str(&format!("unit={}\n", unit.unit_base().unit_name()));
+            for (key, value) in unit.serialize() {
+                ret.push_str(&format!("{}={}\n", key, value));
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    /// ## 读取重新执行之前序列化的状态
+    ///
+    /// 不是由daemon-reexec启动时返回None
+    pub fn deserialize() -> Option<Vec<SerializedUnit>> {
+        let mut args = std::env::args();
+        args.find(|arg| arg == DESERIALIZE_ARG)?;
+        let fd: i32 = args.next()?.parse().ok()?;
+        let mut state = String::new();
+        if let Err(e) = unsafe { File::from_raw_fd(fd) }.read_to_string(&mut state) {
+            eprintln!("Failed to read serialized state: {}", e);
+            return None;
+        }
+
+        let mut units: Vec<SerializedUnit> = Vec::new();
+        for line in state.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some(item) => item,
+                None => continue,
+            };
+            if key == "unit" {
+                units.push(SerializedUnit {
+                    name: value.to_string(),
+                    items: Vec::new(),
+                });
+            } else if let Some(unit) = units.last_mut() {
+                unit.items.push((key.to_string(), value.to_string()));
+            }
+        }
+        Some(units)
+    }
+
+    /// ## 加载重新执行之前已加载的Unit
+    pub fn load_units(units: &[SerializedUnit]) {
+        for unit in units {
+            if let Err(e) = UnitParseUtil::parse_unit_no_type(&unit.name) {
+                eprintln!("Failed to load {}: {}", unit.name, e.error_format());
+            }
+        }
+    }
+
+    /// ## 恢复各Unit的状态，并接管仍在运行的进程
+    ///
+    /// 在load_units加载Unit并推导依赖之后调用
+    pub fn restore(units: Vec<SerializedUnit>) {
+        let mut restored = Vec::new();
+        for serialized in units {
+            let unit = match UnitManager::get_unit_with_name(&serialized.name) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            let mut guard = unit.lock().unwrap();
+            for (key, value) in serialized.items.iter() {
+                guard.deserialize_item(key, value);
+            }
+            drop(guard);
+            restored.push(unit);
+        }
+        // 所有Unit的状态恢复后再接管进程，接管时可能查询其他Unit的状态
+        for unit in restored {
+            unit.lock().unwrap().coldplug();
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fmt, fs,
+    sync::RwLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use nix::{
+    mount::{mount, umount2, MntFlags, MsFlags},
+    sys::{
+        reboot::{reboot, RebootMode},
+        signal::{kill, Signal},
+    },
+    unistd::{getpid, pause, sync},
+};
+
+use crate::{
+    error::{
+        runtime_error::{RuntimeError, RuntimeErrorType},
+        ErrorFormat,
+    },
+    parse::{lookup::UnitLookup, parse_util::UnitParseUtil, UnitParser},
+};
+
+use super::{
+    job_manager::{JobManager, JobMode, JobType},
+    process_manager::ProcessManager,
+};
+
+/// 关机时启动的目标
+const SHUTDOWN_TARGET: &str = "shutdown.target";
+/// 作为1号进程收到SIGINT(ctrl-alt-del)时启动的目标，不存在时直接重启
+const CTRL_ALT_DEL_TARGET: &str = "ctrl-alt-del.target";
+/// 发送SIGTERM后等待剩余进程退出的时间，超时后发送SIGKILL
+const FINAL_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+/// 不卸载的API文件系统
+const API_MOUNTS: &[&str] = &["/proc", "/sys", "/dev", "/run"];
+
+lazy_static! {
+    /// 正在进行的关机操作
+    static ref SHUTDOWN_ACTION: RwLock<Option<ShutdownAction>> = RwLock::new(None);
+}
+
+/// 关机操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownAction {
+    Reboot,
+    Halt,
+    Poweroff,
+    Kexec,
+    // 退出DragonReach，作为1号进程时等同于poweroff
+    Exit,
+}
+
+impl fmt::Display for ShutdownAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ShutdownAction::Reboot => "reboot",
+            ShutdownAction::Halt => "halt",
+            ShutdownAction::Poweroff => "poweroff",
+            ShutdownAction::Kexec => "kexec",
+            ShutdownAction::Exit => "exit",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+pub struct ShutdownManager;
+
+impl ShutdownManager {
+    /// ## 开始关机
+    ///
+    /// 以isolate模式启动shutdown.target，按照依赖关系的逆序停止其余所有Unit，
+    /// 所有作业完成后由主循环调用check完成关机。force为true时跳过停止Unit的阶段
+    pub fn begin(action: ShutdownAction, force: bool) -> Result<(), RuntimeError> {
+        if Self::is_shutting_down() {
+            eprintln!("Shutdown is already in progress");
+            return Err(RuntimeError::new(RuntimeErrorType::InvalidInput));
+        }
+        eprintln!("Starting {}", action);
+
+        if force {
+            Self::finish(action);
+        }
+
+        let target = match UnitParseUtil::parse_unit_no_type(SHUTDOWN_TARGET) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                eprintln!("Failed to load {}: {}", SHUTDOWN_TARGET, e.error_format());
+                None
+            }
+        };
+        match target.map(|id| JobManager::add_job(id, JobType::Start, JobMode::Isolate)) {
+            Some(Ok(_)) => {}
+            // 无法提交关机事务时直接结束
+            _ => Self::finish(action),
+        }
+        *SHUTDOWN_ACTION.write().unwrap() = Some(action);
+        Ok(())
+    }
+
+    /// 是否正在关机，关机期间不再接受新的启动作业
+    pub fn is_shutting_down() -> bool {
+        SHUTDOWN_ACTION.read().unwrap().is_some()
+    }
+
+    /// ## 检查关机事务是否完成，完成则结束关机
+    ///
+    /// 在主循环每次被唤醒时调用
+    pub fn check() {
+        let action = match *SHUTDOWN_ACTION.read().unwrap() {
+            Some(action) => action,
+            None => return,
+        };
+        if JobManager::jobs().is_empty() {
+            Self::finish(action);
+        }
+    }
+
+    /// ## 作为1号进程收到SIGINT时启动ctrl-alt-del.target
+    ///
+    /// 没有ctrl-alt-del.target或无法启动时直接重启，被屏蔽时忽略
+    pub fn ctrl_alt_del() {
+        if UnitLookup::is_masked(CTRL_ALT_DEL_TARGET) {
+            eprintln!("{} is masked, ignoring ctrl-alt-del", CTRL_ALT_DEL_TARGET);
+            return;
+        }
+        if !UnitParser::unit_exists(CTRL_ALT_DEL_TARGET) {
+            let _ = Self::begin(ShutdownAction::Reboot, false);
+            return;
+        }
+        let result = UnitParseUtil::parse_unit_no_type(CTRL_ALT_DEL_TARGET)
+            .map_err(|e| e.error_format())
+            .and_then(|id| {
+                JobManager::add_job(id, JobType::Start, JobMode::Replace)
+                    .map_err(|e| e.error_format())
+            });
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to start {}: {}, rebooting instead",
+                CTRL_ALT_DEL_TARGET, e
+            );
+            let _ = Self::begin(ShutdownAction::Reboot, false);
+        }
+    }
+
+    /// ## 结束剩余进程，卸载文件系统并执行关机操作
+    fn finish(action: ShutdownAction) -> ! {
+        Self::kill_remaining();
+        let pid1 = getpid().as_raw() == 1;
+        if pid1 {
+            Self::unmount_all();
+        }
+        sync();
+
+        // 不作为1号进程运行时不能关闭系统，只退出DragonReach
+        if !pid1 {
+            eprintln!("Not running as PID 1, exiting instead of {}", action);
+            std::process::exit(0);
+        }
+        let mode = match action {
+            ShutdownAction::Reboot => RebootMode::RB_AUTOBOOT,
+            ShutdownAction::Halt => RebootMode::RB_HALT_SYSTEM,
+            ShutdownAction::Kexec => RebootMode::RB_KEXEC,
+            ShutdownAction::Poweroff | ShutdownAction::Exit => RebootMode::RB_POWER_OFF,
+        };
+        let mut err = reboot(mode).unwrap_err();
+        // 未加载kexec内核时RB_KEXEC会失败，此时改为普通重启
+        if action == ShutdownAction::Kexec {
+            eprintln!("{} failed: {}, rebooting instead", action, err);
+            err = reboot(RebootMode::RB_AUTOBOOT).unwrap_err();
+        }
+        // 1号进程退出会导致内核panic，关机失败时只能停在这里
+        eprintln!("{} failed: {}, freezing", action, err);
+        loop {
+            pause();
+        }
+    }
+
+    /// ## 向剩余的进程发送SIGTERM，超时后发送SIGKILL
+    fn kill_remaining() {
+        let signals = [
+            (Signal::SIGTERM, FINAL_KILL_TIMEOUT),
+            (Signal::SIGKILL, FINAL_KILL_TIMEOUT),
+        ];
+        for (signal, timeout) in signals {
+            let pids = ProcessManager::descendants();
+            if pids.is_empty() {
+                return;
+            }
+            eprintln!("Sending {} to {} remaining processes", signal, pids.len());
+            for pid in pids {
+                let _ = kill(pid, signal);
+                let _ = kill(pid, Signal::SIGCONT);
+            }
+            let deadline = Instant::now() + timeout;
+            while Instant::now() < deadline {
+                ProcessManager::reap();
+                if ProcessManager::descendants().is_empty() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    /// ## 按挂载的逆序卸载文件系统，无法卸载时重新挂载为只读
+    fn unmount_all() {
+        let mounts = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+        let points: Vec<String> = mounts
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(|point| point.replace("\\040", " "))
+            .collect();
+        for point in points.iter().rev() {
+            let api = API_MOUNTS
+                .iter()
+                .any(|api| point == api || point.starts_with(&format!("{}/", api)));
+            if api || point == "/" {
+                continue;
+            }
+            if umount2(point.as_str(), MntFlags::empty()).is_err() {
+                Self::remount_read_only(point);
+            }
+        }
+        Self::remount_read_only("/");
+    }
+
+    fn remount_read_only(point: &str) {
+        if let Err(e) = mount(
+            None::<&str>,
+            point,
+            None::<&str>,
+            MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+            None::<&str>,
+        ) {
+            eprintln!("Failed to remount {} read-only: {}", point, e);
+        }
+    }
+}
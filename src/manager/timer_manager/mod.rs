@@ -1,10 +1,11 @@
 use std::{
     sync::{Arc, Mutex, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    error::runtime_error::RuntimeError, time::timer::Timer, unit::timer::TimerUnit, unit::Unit,
+    error::runtime_error::RuntimeError, manager::UnitManager, time::timer::Timer,
+    unit::timer::TimerUnit, unit::Unit, unit::UnitState,
 };
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
@@ -53,11 +54,11 @@ impl TimerManager {
         let timemanager = TIMER_TASK_MANAGER.write().unwrap();
         let mut unit_guard = unit.lock().unwrap();
         let unit_id = unit_guard.unit_id();
-        timemanager
-            .id_table
-            .write()
-            .unwrap()
-            .push((unit_id, unit_guard.get_parent_unit()));
+        // 重新加载的TimerUnit替换原有的计时器
+        let mut id_table = timemanager.id_table.write().unwrap();
+        id_table.retain(|(id, _)| *id != unit_id);
+        id_table.push((unit_id, unit_guard.get_parent_unit()));
+        drop(id_table);
         drop(unit_guard);
         timemanager
             .timer_unit_map
@@ -68,12 +69,17 @@ impl TimerManager {
 
     /// ## 检测定时器是否到时，到时则触发
     ///
-    /// 该方法在主循环中每循环一次检测一次，是伪计时器的主运行函数
+    /// 该方法在主循环每次被唤醒时检测一次，是伪计时器的主运行函数
     pub fn check_timer() {
         let mut writer = TIMER_TASK_MANAGER.write().unwrap();
-        //此处触发定时器，若定时器被触发，则移除
-        writer.inner_timers.retain_mut(|x| !x.check());
+        //此处取出到时的定时器并移除，释放锁之后再触发，定时任务中可以再添加或取消定时器
+        let (expired, pending): (Vec<Timer>, Vec<Timer>) =
+            writer.inner_timers.drain(..).partition(|x| x.is_expired());
+        writer.inner_timers = pending;
         drop(writer);
+        for mut timer in expired {
+            timer.run();
+        }
         //此处触发Timer_unit,不移除
         let reader = TIMER_TASK_MANAGER.read().unwrap();
         let timer_unit_map = reader.timer_unit_map.read().unwrap();
@@ -93,15 +99,46 @@ impl TimerManager {
             }
         }
 
-        for id in inactive_unit {
+        let inactive_unit: Vec<(usize, Arc<Mutex<TimerUnit>>)> = inactive_unit
+            .into_iter()
+            .map(|id| (id, timer_unit_map.get(&id).unwrap().clone()))
+            .collect();
+        // 移除计时器需要写锁，先释放读锁
+        drop(timer_unit_map);
+        drop(reader);
+        for (id, timer_unit) in inactive_unit {
             //处理Inactive需要退出的计时器
             //println!("Prepared to exit...");
-            timer_unit_map.get(&id).unwrap().lock().unwrap().exit();
+            timer_unit.lock().unwrap().exit();
 
             TimerManager::remove_timer_unit(id);
+            // Unit表中的TimerUnit与计时中的相互独立，同步其失效状态，重新加载时据此不再计时
+            if let Some(unit) = UnitManager::get_unit_with_id(&id) {
+                unit.lock()
+                    .unwrap()
+                    .unit_base_mut()
+                    .set_state(UnitState::Inactive);
+            }
         }
     }
 
+    /// ## 获取最近的到期时间，主循环据此设置timerfd
+    ///
+    /// 没有需要等待的计时器时返回None
+    pub fn next_deadline() -> Option<Instant> {
+        let manager = TIMER_TASK_MANAGER.read().unwrap();
+        let timer_unit_map = manager.timer_unit_map.read().unwrap();
+        let units = timer_unit_map
+            .values()
+            .filter_map(|unit| unit.lock().unwrap().next_elapse());
+        manager
+            .inner_timers
+            .iter()
+            .map(|timer| timer.deadline())
+            .chain(units)
+            .min()
+    }
+
     /// ## 取消掉一个unit的所有定时任务，
     ///
     /// 一般在unit启动失败或者退出unit时进行该操作
@@ -110,7 +147,7 @@ impl TimerManager {
             .write()
             .unwrap()
             .inner_timers
-            .retain(|x| x.parent() == unit_id)
+            .retain(|x| x.parent() != unit_id)
     }
 
     pub fn is_timer(id: &usize) -> bool {
@@ -155,6 +192,13 @@ impl TimerManager {
         }
     }
 
+    /// 获取正在计时的TimerUnit，其状态与Unit表中解析得到的TimerUnit相互独立
+    pub fn get_timer_unit(unit_id: usize) -> Option<Arc<Mutex<TimerUnit>>> {
+        let manager = TIMER_TASK_MANAGER.read().unwrap();
+        let timer_unit_map = manager.timer_unit_map.read().unwrap();
+        timer_unit_map.get(&unit_id).cloned()
+    }
+
     /// 获得该id下的所有计时器
     pub fn get_timer(parent_id: usize) -> Vec<usize> {
         let mut result = Vec::new();
@@ -1,13 +1,17 @@
 use std::{
     collections::{hash_map::DefaultHasher, VecDeque},
     hash::{Hash, Hasher},
-    process::Child,
     sync::{Arc, Mutex, RwLock},
 };
 
-use crate::unit::Unit;
+use super::process_manager::ProcessManager;
+use crate::{
+    task::stdio::{StdInput, StdioConfig},
+    unit::{Unit, UnitPart},
+};
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
+use nix::unistd::Pid;
 
 lazy_static! {
     /// 对于启动后即使退出亦认为其为运行状态的特殊注册类Service,对于这类进程做一个标记
@@ -22,24 +26,24 @@ lazy_static! {
     /// 辅助表，通过服务名映射其id
     static ref NAME_TO_UNIT_MAP: RwLock<HashMap<u64,usize>> = RwLock::new(HashMap::new());
 
-    /// 全局运行中的Unit表
+    /// 全局运行中的Unit表，记录Unit的主进程
     pub(super) static ref RUNNING_TABLE: RwLock<RunningTableManager> = RwLock::new(RunningTableManager { running_table: HashMap::new() });
 
-    /// CMD进程表，用于处理Unit的CMD派生进程(ExecStartPre等命令派生进程)
-    pub(super) static ref CMD_PROCESS_TABLE: RwLock<HashMap<u32,Mutex<Child>>> = RwLock::new(HashMap::new());
+    /// 终端占用表，终端路径映射到以其为控制终端的Unit，记录其标准输入输出设置用于退出后重置终端
+    static ref TTY_OWNER_TABLE: RwLock<HashMap<String,(usize,StdioConfig)>> = RwLock::new(HashMap::new());
 }
 
 pub struct RunningTableManager {
-    running_table: HashMap<usize, Child>,
+    running_table: HashMap<usize, Pid>,
 }
 
 #[allow(dead_code)]
 impl RunningTableManager {
-    pub fn running_table(&self) -> &HashMap<usize, Child> {
+    pub fn running_table(&self) -> &HashMap<usize, Pid> {
         &self.running_table
     }
 
-    pub fn mut_running_table(&mut self) -> &mut HashMap<usize, Child> {
+    pub fn mut_running_table(&mut self) -> &mut HashMap<usize, Pid> {
         &mut self.running_table
     }
 }
@@ -58,6 +62,14 @@ impl UnitManager {
         NAME_TO_UNIT_MAP.write().unwrap().insert(hash, unit);
     }
 
+    /// 删除一条path到unit_id的映射
+    pub fn remove_from_name_table(path: &str) {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        let hash = hasher.finish();
+        NAME_TO_UNIT_MAP.write().unwrap().remove(&hash);
+    }
+
     // 判断当前是否已经有了对应path的Unit
     pub fn contains_name(path: &str) -> bool {
         let mut hasher = DefaultHasher::new();
@@ -113,7 +125,7 @@ impl UnitManager {
     }
 
     // 向运行表中添加运行的Unit
-    pub fn push_running(unit_id: usize, p: Child) {
+    pub fn push_running(unit_id: usize, p: Pid) {
         RUNNING_TABLE
             .write()
             .unwrap()
@@ -125,6 +137,47 @@ impl UnitManager {
     pub fn remove_running(id: usize) {
         let mut table = RUNNING_TABLE.write().unwrap();
         table.running_table.remove(&id);
+        drop(table);
+        Self::release_tty(id);
+    }
+
+    /// ## 为Unit占用其标准输入对应的终端
+    ///
+    /// 同一终端同时只能被一个Unit占用，StandardInput=tty-force时从原占用者处强制取得
+    ///
+    /// 终端已被其他Unit占用时返回false
+    pub fn acquire_tty(id: usize, stdio: &StdioConfig) -> bool {
+        if !stdio.is_tty_input() {
+            return true;
+        }
+        let mut table = TTY_OWNER_TABLE.write().unwrap();
+        let path = stdio.tty_path();
+        if let Some((owner, _)) = table.get(path) {
+            if *owner != id && stdio.input != StdInput::TtyForce {
+                return false;
+            }
+        }
+        table.insert(path.to_string(), (id, stdio.clone()));
+        true
+    }
+
+    /// ## 释放Unit占用的终端，并按照TTYReset=等设置重置终端
+    pub fn release_tty(id: usize) {
+        let mut table = TTY_OWNER_TABLE.write().unwrap();
+        let paths: Vec<String> = table
+            .iter()
+            .filter(|(_, (owner, _))| *owner == id)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let released: Vec<StdioConfig> = paths
+            .iter()
+            .filter_map(|path| table.remove(path))
+            .map(|(_, stdio)| stdio)
+            .collect();
+        drop(table);
+        for stdio in released {
+            stdio.reset_tty();
+        }
     }
 
     // 向id到Unit映射表中插入数据
@@ -135,6 +188,21 @@ impl UnitManager {
         }
     }
 
+    // 以重新解析得到的Unit替换id对应的Unit
+    pub fn replace_unit(id: usize, unit: Arc<Mutex<dyn Unit>>) {
+        ID_TO_UNIT_MAP.write().unwrap().insert(id, unit);
+    }
+
+    // 从id到Unit映射表中删除Unit，其名称需另行从名称表中删除或改为映射到其他Unit
+    pub fn remove_unit(id: usize) {
+        ID_TO_UNIT_MAP.write().unwrap().remove(&id);
+    }
+
+    // 获取所有Unit的id
+    pub fn get_all_units() -> Vec<usize> {
+        ID_TO_UNIT_MAP.read().unwrap().keys().cloned().collect()
+    }
+
     // 判断当前DragonReach是否拥有目标id的Unit
     pub fn contains_id(id: &usize) -> bool {
         ID_TO_UNIT_MAP.read().unwrap().contains_key(id)
@@ -175,52 +243,52 @@ impl UnitManager {
         return RUNNING_TABLE.read().unwrap().running_table.len();
     }
 
-    // 向Cmd运行表中添加
-    pub fn push_cmd_proc(proc: Child) {
-        CMD_PROCESS_TABLE
-            .write()
+    // 获取运行中Unit的主进程
+    pub fn running_pid(id: usize) -> Option<Pid> {
+        RUNNING_TABLE
+            .read()
             .unwrap()
-            .insert(proc.id(), Mutex::new(proc));
-    }
-
-    // 弹出指定id的cmd进程
-    pub fn pop_cmd_proc(id: u32) -> Option<Mutex<Child>> {
-        CMD_PROCESS_TABLE.write().unwrap().remove(&id)
+            .running_table
+            .get(&id)
+            .copied()
     }
 
-    // 初始化各Unit的依赖关系，此方法只需在解析完系统Unit文件后调用一次
+    /// ## 根据各Unit声明的依赖推导反向依赖
+    ///
+    /// 包括RequiredBy、WantedBy、After(由Before推导)、BoundBy、ConsistsOf、ConflictedBy。
+    /// [Install]段的WantedBy=、RequiredBy=只在Unit被启用后通过.wants/、.requires/目录中的链接生效，此处不做处理。
+    /// 每次加载新的Unit后调用，已存在的依赖不会重复添加
     pub fn init_units_dependencies() {
-        let manager = ID_TO_UNIT_MAP.write().unwrap();
-
-        // 处理before段，将before段的Unit添加此Unit为After
-        for (id, unit) in manager.iter() {
-            let mut unit = unit.lock().unwrap();
-            let before = unit.unit_base_mut().unit_part().before();
-            for rid in before {
-                let req = UnitManager::get_unit_with_id(rid).unwrap();
-                let mut req = req.lock().unwrap();
-                req.unit_base_mut().mut_unit_part().push_after_unit(*id);
+        // 先复制依赖信息再逐个修改，避免同时持有多个Unit的锁
+        let units: Vec<(usize, UnitPart)> = ID_TO_UNIT_MAP
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, unit)| (*id, unit.lock().unwrap().unit_base().unit_part().clone()))
+            .collect();
+
+        // 在被依赖的Unit上添加反向依赖的方法
+        type PushInverse = fn(&mut UnitPart, usize);
+        for (id, part) in units {
+            let edges: [(&[usize], PushInverse); 6] = [
+                (part.requires(), UnitPart::push_required_by),
+                (part.wants(), UnitPart::push_wanted_by),
+                (part.before(), UnitPart::push_after_unit),
+                (part.binds_to(), UnitPart::push_be_binded_by),
+                (part.part_of(), UnitPart::push_consists_of),
+                (part.conflicts(), UnitPart::push_conflicted_by),
+            ];
+            for (targets, push) in edges {
+                for target in targets {
+                    Self::update_unit_part(*target, |part| push(part, id));
+                }
             }
         }
+    }
 
-        for (id, unit) in manager.iter() {
-            let mut unit = unit.lock().unwrap();
-
-            // 处理binds_to段
-            let binds_to = unit.unit_base_mut().unit_part().binds_to();
-            for rid in binds_to {
-                let req = UnitManager::get_unit_with_id(rid).unwrap();
-                let mut req = req.lock().unwrap();
-                req.unit_base_mut().mut_unit_part().push_be_binded_by(*id);
-            }
-
-            // 处理part_of段
-            let part_of = unit.unit_base_mut().unit_part().part_of();
-            for rid in part_of {
-                let req = UnitManager::get_unit_with_id(rid).unwrap();
-                let mut req = req.lock().unwrap();
-                req.unit_base_mut().mut_unit_part().push_be_binded_by(*id);
-            }
+    fn update_unit_part<F: FnOnce(&mut UnitPart)>(id: usize, f: F) {
+        if let Some(unit) = Self::get_unit_with_id(&id) {
+            f(unit.lock().unwrap().unit_base_mut().mut_unit_part());
         }
     }
 
@@ -235,9 +303,11 @@ impl UnitManager {
 
     pub fn kill_running(id: usize) {
         let mut running_manager = RUNNING_TABLE.write().unwrap();
-        let unit = running_manager.running_table.get_mut(&id).unwrap();
-        let _ = unit.kill();
+        // 终止该Unit的整个进程树，而不仅是主进程
+        ProcessManager::kill_unit(id);
         println!("kill:{}", id);
         running_manager.running_table.remove(&id);
+        drop(running_manager);
+        Self::release_tty(id);
     }
 }
@@ -0,0 +1,78 @@
+/// ## 内置的标准Unit
+///
+/// 单元目录中不存在同名文件时使用这些定义，使发行版提供的Unit文件中
+/// After=network.target、WantedBy=multi-user.target等引用能够被解析
+const BUILTIN_UNITS: &[(&str, &str)] = &[
+    (
+        "sysinit.target",
+        "[Unit]
+Description=System Initialization
+DefaultDependencies=no
+Conflicts=emergency.target
+After=emergency.target
+",
+    ),
+    (
+        "basic.target",
+        "[Unit]
+Description=Basic System
+Requires=sysinit.target
+Wants=timers.target
+After=sysinit.target timers.target
+",
+    ),
+    (
+        "multi-user.target",
+        "[Unit]
+Description=Multi-User System
+Requires=basic.target
+Conflicts=rescue.target
+After=basic.target rescue.target
+",
+    ),
+    (
+        "shutdown.target",
+        "[Unit]
+Description=System Shutdown
+DefaultDependencies=no
+",
+    ),
+    (
+        "rescue.target",
+        "[Unit]
+Description=Rescue Mode
+Requires=sysinit.target
+After=sysinit.target
+",
+    ),
+    (
+        "emergency.target",
+        "[Unit]
+Description=Emergency Mode
+DefaultDependencies=no
+",
+    ),
+    (
+        "network.target",
+        "[Unit]
+Description=Network
+",
+    ),
+    (
+        "timers.target",
+        "[Unit]
+Description=Timer Units
+DefaultDependencies=no
+Conflicts=shutdown.target
+Before=shutdown.target
+",
+    ),
+];
+
+/// ## 获取内置Unit的定义
+pub fn builtin_unit(name: &str) -> Option<&'static str> {
+    BUILTIN_UNITS
+        .iter()
+        .find(|(unit, _)| *unit == name)
+        .map(|(_, content)| *content)
+}
@@ -0,0 +1,149 @@
+use std::{fs, path::Path};
+
+use super::parse_util::UnitParseUtil;
+
+/// 管理员配置的Unit目录
+pub const ADMIN_UNIT_DIR: &str = "/etc/reach/system/";
+/// 运行时及临时Unit目录，重启后失效
+pub const RUNTIME_UNIT_DIR: &str = "/run/reach/system/";
+/// 软件包提供的Unit目录
+pub const VENDOR_UNIT_DIR: &str = "/usr/lib/reach/system/";
+
+/// ## Unit搜索路径
+///
+/// 按优先级从高到低排列，靠前目录中的同名文件覆盖靠后目录中的文件。
+/// generator.early、generator与generator.late为生成器的输出目录
+pub const UNIT_SEARCH_PATH: &[&str] = &[
+    "/run/reach/generator.early/",
+    ADMIN_UNIT_DIR,
+    RUNTIME_UNIT_DIR,
+    "/run/reach/generator/",
+    VENDOR_UNIT_DIR,
+    "/run/reach/generator.late/",
+];
+
+pub struct UnitLookup;
+
+impl UnitLookup {
+    /// ## 在搜索路径中查找Unit文件
+    ///
+    /// name为路径时直接检查该文件
+    ///
+    /// ### return 找到则返回优先级最高的文件路径
+    pub fn find(name: &str) -> Option<String> {
+        if name.contains('/') {
+            return UnitParseUtil::is_valid_file(name).then(|| name.to_string());
+        }
+        UNIT_SEARCH_PATH
+            .iter()
+            .map(|dir| format!("{}{}", dir, name))
+            .find(|path| UnitParseUtil::is_valid_file(path))
+    }
+
+    /// ## 列出搜索路径中所有的Unit文件名
+    ///
+    /// 同名文件只列出一次
+    pub fn list_unit_files() -> Vec<String> {
+        let mut names = Vec::new();
+        for dir in UNIT_SEARCH_PATH {
+            for name in Self::read_dir(dir) {
+                let path = format!("{}{}", dir, name);
+                if UnitParseUtil::is_valid_file(&path) && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// ## 获取各搜索路径中<name>.<suffix>/目录下的条目名
+    ///
+    /// 所有目录中的条目合并在一起，用于.wants/与.requires/目录
+    pub fn dir_entries(name: &str, suffix: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        for dir in UNIT_SEARCH_PATH {
+            for entry in Self::read_dir(&format!("{}{}.{}/", dir, name, suffix)) {
+                if !names.contains(&entry) {
+                    names.push(entry);
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// ## 获取优先级最高的同名符号链接所指向的Unit名
+    ///
+    /// 优先级最高的同名文件不是符号链接时返回None
+    pub fn link_target(name: &str) -> Option<String> {
+        for dir in UNIT_SEARCH_PATH {
+            let path = format!("{}{}", dir, name);
+            if fs::symlink_metadata(&path).is_err() {
+                continue;
+            }
+            let target = fs::read_link(&path).ok()?;
+            return target
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.to_string());
+        }
+        None
+    }
+
+    /// ## 解析以符号链接定义的别名
+    ///
+    /// 优先级最高的同名文件是指向另一个同类型Unit文件的符号链接时，返回最终指向的Unit名
+    pub fn resolve_alias(name: &str) -> Option<String> {
+        if name.contains('/') {
+            return None;
+        }
+        let path = UNIT_SEARCH_PATH
+            .iter()
+            .map(|dir| format!("{}{}", dir, name))
+            .find(|path| fs::symlink_metadata(path).is_ok())?;
+        if !fs::symlink_metadata(&path).ok()?.file_type().is_symlink() {
+            return None;
+        }
+        let target = fs::canonicalize(&path).ok()?;
+        let target = target.file_name()?.to_str()?;
+        let suffix = |name: &str| name.rsplit_once('.').map(|(_, suffix)| suffix.to_string());
+        if target == name || suffix(target) != suffix(name) {
+            return None;
+        }
+        Some(target.to_string())
+    }
+
+    /// ## Unit是否被屏蔽
+    ///
+    /// 优先级最高的同名文件是指向/dev/null的符号链接时被屏蔽。
+    /// 管理员目录及运行时目录中的屏蔽链接总是生效，不会被其他目录中的同名文件覆盖
+    pub fn is_masked(name: &str) -> bool {
+        if name.contains('/') {
+            return false;
+        }
+        let is_mask = |path: &String| {
+            fs::read_link(path).map_or(false, |target| target == Path::new("/dev/null"))
+        };
+        let config = [ADMIN_UNIT_DIR, RUNTIME_UNIT_DIR]
+            .iter()
+            .map(|dir| format!("{}{}", dir, name))
+            .any(|path| is_mask(&path));
+        config
+            || UNIT_SEARCH_PATH
+                .iter()
+                .map(|dir| format!("{}{}", dir, name))
+                .find(|path| fs::symlink_metadata(path).is_ok())
+                .map_or(false, |path| is_mask(&path))
+    }
+
+    fn read_dir(dir: &str) -> Vec<String> {
+        match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str().map(|s| s.to_string()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
@@ -5,22 +5,30 @@ use std::sync::{Arc, Mutex};
 use crate::error::parse_error::ParseErrorType;
 use crate::manager::UnitManager;
 use crate::unit::timer::TimerUnitAttr;
-use crate::unit::{BaseUnit, Unit};
-use crate::DRAGON_REACH_UNIT_DIR;
+use crate::unit::{generate_unit_id, BaseUnit, LoadState, Unit};
 use crate::{
-    error::parse_error::ParseError,
-    unit::{service::ServiceUnitAttr, BaseUnitAttr, InstallUnitAttr, UnitType},
+    error::{parse_error::ParseError, ErrorFormat},
+    unit::{
+        service::{ServiceUnit, ServiceUnitAttr},
+        target::TargetUnit,
+        timer::TimerUnit,
+        BaseUnitAttr, InstallUnitAttr, UnitType,
+    },
 };
 
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
+use nix::sys::resource::Resource;
 
+use self::builtin::builtin_unit;
+use self::lookup::{UnitLookup, ADMIN_UNIT_DIR};
 use self::parse_service::ServiceParser;
 use self::parse_target::TargetParser;
 use self::parse_timer::TimerParser;
 use self::parse_util::UnitParseUtil;
 
-pub mod graph;
+pub mod builtin;
+pub mod lookup;
 pub mod parse_service;
 pub mod parse_target;
 pub mod parse_timer;
@@ -37,6 +45,9 @@ pub enum Segment {
 }
 
 lazy_static! {
+    /// 已被别名的提供者替换的占位Unit，记录占位Unit的id到提供者id的映射
+    static ref REPLACED_PLACEHOLDERS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
     pub static ref UNIT_SUFFIX: HashMap<&'static str, UnitType> = {
         let mut table = HashMap::new();
         table.insert("automount", UnitType::Automount);
@@ -92,20 +103,66 @@ lazy_static! {
         unit_attr_table.insert("User", ServiceUnitAttr::User);
         unit_attr_table.insert("Group", ServiceUnitAttr::Group);
         unit_attr_table.insert("MountFlags", ServiceUnitAttr::MountFlags);
+        unit_attr_table.insert("CPUSchedulingPolicy", ServiceUnitAttr::CPUSchedulingPolicy);
+        unit_attr_table.insert("CPUSchedulingPriority", ServiceUnitAttr::CPUSchedulingPriority);
+        unit_attr_table.insert("CPUAffinity", ServiceUnitAttr::CPUAffinity);
+        unit_attr_table.insert("IOSchedulingClass", ServiceUnitAttr::IOSchedulingClass);
+        unit_attr_table.insert("IOSchedulingPriority", ServiceUnitAttr::IOSchedulingPriority);
+        unit_attr_table.insert("OOMScoreAdjust", ServiceUnitAttr::OOMScoreAdjust);
+        unit_attr_table.insert("UMask", ServiceUnitAttr::UMask);
+        unit_attr_table.insert("StandardInput", ServiceUnitAttr::StandardInput);
+        unit_attr_table.insert("StandardOutput", ServiceUnitAttr::StandardOutput);
+        unit_attr_table.insert("StandardError", ServiceUnitAttr::StandardError);
+        unit_attr_table.insert("StandardInputText", ServiceUnitAttr::StandardInputText);
+        unit_attr_table.insert("StandardInputData", ServiceUnitAttr::StandardInputData);
+        unit_attr_table.insert("TTYPath", ServiceUnitAttr::TTYPath);
+        unit_attr_table.insert("TTYReset", ServiceUnitAttr::TTYReset);
+        unit_attr_table.insert("TTYVHangup", ServiceUnitAttr::TTYVHangup);
+        unit_attr_table.insert("TTYVTDisallocate", ServiceUnitAttr::TTYVTDisallocate);
+        unit_attr_table.insert("KillMode", ServiceUnitAttr::KillMode);
+        unit_attr_table.insert("KillSignal", ServiceUnitAttr::KillSignal);
+        unit_attr_table.insert("SendSIGKILL", ServiceUnitAttr::SendSIGKILL);
+        for (name, resource) in RLIMIT_TABLE.iter() {
+            unit_attr_table.insert(*name, ServiceUnitAttr::Limit(*resource));
+        }
         unit_attr_table
     };
+    /// Limit*= 属性名到资源类型的映射，DefaultLimit*= 同样使用该表
+    pub static ref RLIMIT_TABLE: HashMap<&'static str, Resource> = {
+        let mut table = HashMap::new();
+        table.insert("LimitCPU", Resource::RLIMIT_CPU);
+        table.insert("LimitFSIZE", Resource::RLIMIT_FSIZE);
+        table.insert("LimitDATA", Resource::RLIMIT_DATA);
+        table.insert("LimitSTACK", Resource::RLIMIT_STACK);
+        table.insert("LimitCORE", Resource::RLIMIT_CORE);
+        table.insert("LimitRSS", Resource::RLIMIT_RSS);
+        table.insert("LimitNOFILE", Resource::RLIMIT_NOFILE);
+        table.insert("LimitAS", Resource::RLIMIT_AS);
+        table.insert("LimitNPROC", Resource::RLIMIT_NPROC);
+        table.insert("LimitMEMLOCK", Resource::RLIMIT_MEMLOCK);
+        table.insert("LimitLOCKS", Resource::RLIMIT_LOCKS);
+        table.insert("LimitSIGPENDING", Resource::RLIMIT_SIGPENDING);
+        table.insert("LimitMSGQUEUE", Resource::RLIMIT_MSGQUEUE);
+        table.insert("LimitNICE", Resource::RLIMIT_NICE);
+        table.insert("LimitRTPRIO", Resource::RLIMIT_RTPRIO);
+        table.insert("LimitRTTIME", Resource::RLIMIT_RTTIME);
+        table
+    };
     pub static ref BASE_UNIT_ATTR_TABLE: HashMap<&'static str, BaseUnitAttr> = {
         let mut unit_attr_table = HashMap::new();
         unit_attr_table.insert("Description", BaseUnitAttr::Description);
         unit_attr_table.insert("Documentation", BaseUnitAttr::Documentation);
         unit_attr_table.insert("Requires", BaseUnitAttr::Requires);
         unit_attr_table.insert("Wants", BaseUnitAttr::Wants);
+        unit_attr_table.insert("Requisite", BaseUnitAttr::Requisite);
         unit_attr_table.insert("After", BaseUnitAttr::After);
         unit_attr_table.insert("Before", BaseUnitAttr::Before);
         unit_attr_table.insert("Binds To", BaseUnitAttr::BindsTo);
         unit_attr_table.insert("Part Of", BaseUnitAttr::PartOf);
         unit_attr_table.insert("OnFailure", BaseUnitAttr::OnFailure);
         unit_attr_table.insert("Conflicts", BaseUnitAttr::Conflicts);
+        unit_attr_table.insert("DefaultDependencies", BaseUnitAttr::DefaultDependencies);
+        unit_attr_table.insert("RequiresMountsFor", BaseUnitAttr::RequiresMountsFor);
         unit_attr_table
     };
     pub static ref BASE_IEC: HashMap<&'static str, u64> = {
@@ -187,11 +244,12 @@ impl UnitParser {
     ///
     /// @return 成功则返回对应BufReader，否则返回Err
     pub fn get_reader(path: &str, unit_type: UnitType) -> Result<io::BufReader<File>, ParseError> {
-        //判断是否为路径，若不为路径则到定向到默认unit文件夹
-        let mut realpath = path.to_string();
-        if !path.contains('/') {
-            realpath = format!("{}{}", DRAGON_REACH_UNIT_DIR, &path).to_string();
-        }
+        //判断是否为路径，若不为路径则在Unit搜索路径中查找
+        let realpath = match UnitLookup::find(path) {
+            Some(realpath) => realpath,
+            None if !path.contains('/') => format!("{}{}", ADMIN_UNIT_DIR, path),
+            None => path.to_string(),
+        };
         let path = realpath.as_str();
         // 如果指定UnitType,则进行文件名检查，不然直接返回reader
         if unit_type != UnitType::Unknown {
@@ -218,6 +276,35 @@ impl UnitParser {
         return Ok(io::BufReader::new(file));
     }
 
+    /// @brief 读取Unit文件的所有行
+    ///
+    /// 搜索路径中不存在该文件时，使用同名的内置Unit定义
+    ///
+    /// @return 成功则返回所有行及实际读取的文件路径，内置Unit的路径为空
+    fn read_lines(path: &str, unit_type: UnitType) -> Result<(Vec<String>, String), ParseError> {
+        let fragment = match UnitLookup::find(path) {
+            Some(fragment) => fragment,
+            None => {
+                if let Some(content) = builtin_unit(path) {
+                    let lines = content.lines().map(|line| line.to_string()).collect();
+                    return Ok((lines, String::new()));
+                }
+                path.to_string()
+            }
+        };
+        let reader = UnitParser::get_reader(&fragment, unit_type)?;
+        let lines = reader
+            .lines()
+            .map(|line| line.unwrap())
+            .collect::<Vec<String>>();
+        Ok((lines, fragment))
+    }
+
+    /// @brief 判断Unit是否存在，包括搜索路径中的文件及内置Unit
+    pub fn unit_exists(path: &str) -> bool {
+        UnitLookup::find(path).is_some() || builtin_unit(path).is_some()
+    }
+
     pub fn from_path(path: &str) -> Result<usize, ParseError> {
         let unit_type = UnitParseUtil::parse_type(&path);
         match unit_type {
@@ -228,6 +315,36 @@ impl UnitParser {
         }
     }
 
+    /// @brief 重新解析已加载的Unit，用于daemon-reload
+    ///
+    /// 沿用原有的id，解析得到的新Unit不会登记到Unit表中，由调用者替换原有的Unit
+    ///
+    /// @param name 已加载的Unit名
+    ///
+    /// @return 解析成功则返回新的Unit，否则返回Err
+    pub fn reparse(name: &str) -> Result<Arc<Mutex<dyn Unit>>, ParseError> {
+        let id = match UnitManager::get_id_with_path(name) {
+            Some(id) => id,
+            None => return Err(ParseError::new(ParseErrorType::EFILE, name.to_string(), 0)),
+        };
+        let unit_type = UnitParseUtil::parse_type(name);
+        match unit_type {
+            UnitType::Service => Self::reparse_as::<ServiceUnit>(name, unit_type, id),
+            UnitType::Target => Self::reparse_as::<TargetUnit>(name, unit_type, id),
+            UnitType::Timer => Self::reparse_as::<TimerUnit>(name, unit_type, id),
+            _ => Err(ParseError::new(ParseErrorType::EFILE, name.to_string(), 0)),
+        }
+    }
+
+    fn reparse_as<T: Unit + Default + Clone + 'static>(
+        name: &str,
+        unit_type: UnitType,
+        id: usize,
+    ) -> Result<Arc<Mutex<dyn Unit>>, ParseError> {
+        let unit = Self::parse_file::<T>(name, unit_type, name.to_string(), id)?;
+        Ok(Arc::new(Mutex::new(unit)))
+    }
+
     /// @brief 将path路径的文件解析为unit_type类型的Unit
     ///
     /// 该方法解析每个Unit共有的段(Unit,Install),其余独有的段属性将会交付T类型的Unit去解析
@@ -245,19 +362,170 @@ impl UnitParser {
             Some(size) => String::from(&path[size..]),
             None => String::from(path),
         };
-        // 如果该文件已解析过，则直接返回id
-        if UnitManager::contains_name(&name) {
-            let unit = UnitManager::get_unit_with_name(&name).unwrap();
-            let unit = unit.lock().unwrap();
-            return Ok(unit.unit_id());
+        // 如果该文件已解析过或正在解析，则直接返回id
+        if let Some(id) = UnitManager::get_id_with_path(&name) {
+            return Ok(id);
+        }
+
+        // 被屏蔽的Unit只登记名称及加载状态，不读取Unit文件
+        if UnitLookup::is_masked(&name) {
+            return Ok(Self::placeholder(&name, LoadState::Masked, String::new()));
+        }
+
+        // 以符号链接定义的别名，加载其指向的Unit并登记别名
+        if let Some(target) = UnitLookup::resolve_alias(&name) {
+            let id = UnitParseUtil::parse_unit_no_type(&target)?;
+            UnitManager::insert_into_name_table(&name, id);
+            return Ok(id);
+        }
+
+        // 解析依赖项之前预先分配id并登记名称，依赖之间存在环时不会重复解析
+        let id = generate_unit_id();
+        UnitManager::insert_into_name_table(&name, id);
+        let unit = match Self::parse_file::<T>(path, unit_type, name.clone(), id) {
+            Ok(unit) => unit,
+            Err(e) => {
+                UnitManager::remove_from_name_table(&name);
+                return Err(e);
+            }
+        };
+        let dret: Arc<Mutex<dyn Unit>> = Arc::new(Mutex::new(unit));
+        UnitManager::insert_unit_with_id(id, dret);
+        Self::register_aliases(id);
+
+        Ok(id)
+    }
+
+    /// @brief 登记Unit在Alias=中定义的别名
+    ///
+    /// 与Unit类型不同或已被其他Unit使用的别名将被忽略。
+    /// 别名已被引用而创建了not found的占位Unit时，以当前Unit替换该占位Unit
+    pub fn register_aliases(id: usize) {
+        let unit = match UnitManager::get_unit_with_id(&id) {
+            Some(unit) => unit,
+            None => return,
+        };
+        let guard = unit.lock().unwrap();
+        let base = guard.unit_base();
+        let (name, unit_type) = (base.unit_name(), *base.unit_type());
+        let aliases = base.install_part().alias().to_vec();
+        // 替换占位Unit时需要修改其他Unit，先释放当前Unit的锁
+        drop(guard);
+        for alias in &aliases {
+            if UnitParseUtil::parse_type(alias) != unit_type {
+                eprintln!("{}: ignoring alias {} of a different type", name, alias);
+                continue;
+            }
+            match UnitManager::get_id_with_path(alias) {
+                Some(other) if other != id && Self::is_not_found(other) => {
+                    Self::replace_placeholder(alias, other, id)
+                }
+                Some(other) if other != id => {
+                    eprintln!("{}: alias {} is already used by another unit", name, alias);
+                }
+                Some(_) => {}
+                None => UnitManager::insert_into_name_table(alias, id),
+            }
+        }
+    }
+
+    fn is_not_found(id: usize) -> bool {
+        UnitManager::get_unit_with_id(&id).map_or(false, |unit| {
+            *unit.lock().unwrap().unit_base().load_state() == LoadState::NotFound
+        })
+    }
+
+    /// @brief 以id对应的Unit替换名为name的占位Unit，引用占位Unit的依赖改为引用该Unit
+    fn replace_placeholder(name: &str, placeholder: usize, id: usize) {
+        REPLACED_PLACEHOLDERS
+            .lock()
+            .unwrap()
+            .push((placeholder, id));
+        UnitManager::remove_unit(placeholder);
+        UnitManager::insert_into_name_table(name, id);
+        for other in UnitManager::get_all_units() {
+            if let Some(unit) = UnitManager::get_unit_with_id(&other) {
+                unit.lock().unwrap().replace_dependency(placeholder, id);
+            }
+        }
+    }
+
+    /// @brief 为无法加载的Unit创建占位Unit并登记到Unit表中
+    ///
+    /// 占位Unit只有名称及加载状态，不能被启动。暂不支持的Unit类型使用TargetUnit保存
+    ///
+    /// @param load_error LoadState为Error时的解析错误
+    ///
+    /// @return 占位Unit的id
+    pub fn placeholder(name: &str, load_state: LoadState, load_error: String) -> usize {
+        let id = generate_unit_id();
+        let unit_type = UNIT_SUFFIX
+            .get(name.rsplit('.').next().unwrap_or_default())
+            .copied()
+            .unwrap_or(UnitType::Unknown);
+        let mut unit_base = BaseUnit::default();
+        unit_base.set_unit_type(unit_type);
+        unit_base.set_load_state(load_state);
+        unit_base.set_load_error(load_error);
+        let unit = match unit_type {
+            UnitType::Service => Self::new_placeholder::<ServiceUnit>(name, unit_base, id),
+            UnitType::Timer => Self::new_placeholder::<TimerUnit>(name, unit_base, id),
+            _ => Self::new_placeholder::<TargetUnit>(name, unit_base, id),
+        };
+        UnitManager::insert_into_name_table(name, id);
+        UnitManager::insert_unit_with_id(id, unit);
+        id
+    }
+
+    /// @brief 删除由placeholder创建且未被引用的占位Unit
+    pub fn remove_placeholder(name: &str, id: usize) {
+        UnitManager::remove_from_name_table(name);
+        UnitManager::remove_unit(id);
+    }
+
+    fn new_placeholder<T: Unit + Default + 'static>(
+        name: &str,
+        unit_base: BaseUnit,
+        id: usize,
+    ) -> Arc<Mutex<dyn Unit>> {
+        let mut unit = T::default();
+        unit.set_unit_base(unit_base);
+        unit.set_unit_name(name.to_string());
+        unit.unit_base_mut().set_id(id);
+        Arc::new(Mutex::new(unit))
+    }
+
+    /// @brief 加载<unit>.wants/与<unit>.requires/目录中链接的Unit，作为该Unit的Wants=与Requires=
+    ///
+    /// 合并所有搜索路径中的同名目录，目录中无法加载的Unit只打印错误，不影响当前Unit的加载
+    fn parse_dependency_dirs(name: &str, unit_base: &mut BaseUnit) {
+        let name = name.trim_start_matches('/');
+        let dirs = [
+            ("wants", BaseUnitAttr::Wants),
+            ("requires", BaseUnitAttr::Requires),
+        ];
+        for (suffix, attr) in dirs {
+            for dep in UnitLookup::dir_entries(name, suffix) {
+                if let Err(e) = unit_base.set_unit_part_attr(&attr, &dep) {
+                    eprintln!("Err:{}", e.error_format());
+                }
+            }
         }
+    }
 
+    /// @brief 解析path路径的Unit文件内容
+    fn parse_file<T: Unit + Default + Clone + 'static>(
+        path: &str,
+        unit_type: UnitType,
+        name: String,
+        id: usize,
+    ) -> Result<T, ParseError> {
         let mut unit: T = T::default();
         let mut unit_base = BaseUnit::default();
         //设置unit类型标记
         unit_base.set_unit_type(unit_type);
 
-        let reader = UnitParser::get_reader(path, unit_type)?;
+        let (lines, fragment_path) = Self::read_lines(path, unit_type)?;
 
         //用于记录当前段的类型
         let mut segment = Segment::None;
@@ -265,10 +533,6 @@ impl UnitParser {
         let _last_attr = ServiceUnitAttr::None;
 
         //一行一行向下解析
-        let lines = reader
-            .lines()
-            .map(|line| line.unwrap())
-            .collect::<Vec<String>>();
         let mut i = 0;
         while i < lines.len() {
             let line = &lines[i];
@@ -370,14 +634,82 @@ impl UnitParser {
             i += 1;
         }
 
+        Self::parse_dependency_dirs(&name, &mut unit_base);
+
+        unit_base.set_fragment_path(fragment_path);
         unit.set_unit_base(unit_base);
-        unit.set_unit_name(name.clone());
-        let id = unit.set_unit_id();
+        unit.set_unit_name(name);
+        unit.unit_base_mut().set_id(id);
+        if let Err(e) = unit.add_implicit_dependencies() {
+            let mut e = e.clone();
+            e.set_file(path);
+            return Err(e);
+        }
+        // 解析依赖项时可能有占位Unit被替换，此时当前Unit尚未登记到Unit表中，需在此替换
+        for (from, to) in REPLACED_PLACEHOLDERS.lock().unwrap().iter() {
+            unit.replace_dependency(*from, *to);
+        }
         unit.init();
-        let dret: Arc<Mutex<dyn Unit>> = Arc::new(Mutex::new(unit));
-        UnitManager::insert_unit_with_id(id, dret);
-        UnitManager::insert_into_name_table(&name, id);
 
-        return Ok(id);
+        Ok(unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 创建并登记一个已加载的Service
+    fn service(name: &str, configure: impl FnOnce(&mut BaseUnit)) -> usize {
+        let id = generate_unit_id();
+        let mut unit_base = BaseUnit::default();
+        unit_base.set_unit_type(UnitType::Service);
+        configure(&mut unit_base);
+        let unit = UnitParser::new_placeholder::<ServiceUnit>(name, unit_base, id);
+        UnitManager::insert_into_name_table(name, id);
+        UnitManager::insert_unit_with_id(id, unit);
+        id
+    }
+
+    fn wants(id: usize) -> Vec<usize> {
+        let unit = UnitManager::get_unit_with_id(&id).unwrap();
+        let unit = unit.lock().unwrap();
+        unit.unit_base().unit_part().wants().to_vec()
+    }
+
+    #[test]
+    fn alias_replaces_not_found_placeholder() {
+        let alias = "alias-test-old.service";
+        // 别名先被其他Unit引用，此时只能创建not found的占位Unit
+        let user = service("alias-test-user.service", |base| {
+            base.set_unit_part_attr(&BaseUnitAttr::Wants, alias)
+                .unwrap();
+        });
+        let placeholder = UnitManager::get_id_with_path(alias).unwrap();
+        assert_eq!(wants(user), vec![placeholder]);
+
+        let provider = service("alias-test-new.service", |base| {
+            base.set_install_part_attr(&InstallUnitAttr::Alias, alias)
+                .unwrap();
+        });
+        UnitParser::register_aliases(provider);
+
+        assert_eq!(UnitManager::get_id_with_path(alias), Some(provider));
+        assert!(!UnitManager::contains_id(&placeholder));
+        assert_eq!(wants(user), vec![provider]);
+    }
+
+    #[test]
+    fn alias_keeps_name_of_loaded_unit() {
+        let name = "alias-test-taken.service";
+        let owner = service(name, |_| {});
+        let other = service("alias-test-other.service", |base| {
+            base.set_install_part_attr(&InstallUnitAttr::Alias, name)
+                .unwrap();
+        });
+        UnitParser::register_aliases(other);
+
+        assert_eq!(UnitManager::get_id_with_path(name), Some(owner));
+        assert!(UnitManager::contains_id(&owner));
     }
 }
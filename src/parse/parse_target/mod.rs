@@ -1,8 +1,7 @@
-use super::graph::Graph;
-use super::parse_util::UnitParseUtil;
+use super::UnitParser;
 
 use crate::error::parse_error::ParseError;
-use crate::manager::UnitManager;
+use crate::unit::{target::TargetUnit, UnitType};
 
 pub struct TargetParser;
 
@@ -15,14 +14,6 @@ impl TargetParser {
     ///
     /// @return 成功则返回Ok(Rc<ServiceUnit>)，否则返回Err
     pub fn parse(path: &str) -> Result<usize, ParseError> {
-        //预先检查是否存在循环依赖
-        let mut graph = Graph::construct_graph(path.to_string())?;
-        let ret = graph.topological_sort()?;
-        for p in ret {
-            let _temp_unit = UnitParseUtil::parse_unit_no_type(&p)?;
-        }
-
-        let result = UnitManager::get_id_with_path(path).unwrap();
-        return Ok(result);
+        UnitParser::parse::<TargetUnit>(path, UnitType::Target)
     }
 }
@@ -1,14 +1,29 @@
-use std::{fs, io::BufRead, os::unix::fs::PermissionsExt, path::Path};
+use std::{
+    collections::BTreeSet, fs, io::BufRead, os::unix::fs::PermissionsExt, path::Path, str::FromStr,
+};
+
+use nix::sys::{resource::Resource, signal::Signal};
 
 use crate::{
-    contants::{AF_INET, AF_INET6, IPV4_MIN_MTU, IPV6_MIN_MTU, PRIO_MAX, PRIO_MIN},
-    error::parse_error::{ParseError, ParseErrorType},
-    task::cmdtask::CmdTask,
-    unit::{service::ServiceUnit, target::TargetUnit, timer::TimerUnit, Unit, UnitType, Url},
+    contants::{AF_INET, AF_INET6, CPU_SET_SIZE, IPV4_MIN_MTU, IPV6_MIN_MTU, PRIO_MAX, PRIO_MIN},
+    error::{
+        parse_error::{ParseError, ParseErrorType},
+        ErrorFormat,
+    },
+    manager::UnitManager,
+    task::{
+        cmdtask::CmdTask,
+        exec_context::ResourceLimit,
+        stdio::{StdInput, StdOutput},
+    },
+    unit::{
+        service::ServiceUnit, target::TargetUnit, timer::TimerUnit, LoadState, Unit, UnitType, Url,
+    },
     FileDescriptor,
 };
 
-use super::{UnitParser, BASE_IEC, BASE_SI, SEC_UNIT_TABLE};
+use super::lookup::UnitLookup;
+use super::{UnitParser, BASE_IEC, BASE_SI, SEC_UNIT_TABLE, UNIT_SUFFIX};
 
 #[allow(dead_code)]
 #[derive(PartialEq)]
@@ -180,7 +195,12 @@ impl UnitParseUtil {
         let (integer, fraction) = match number_str.find(".") {
             Some(mid) => {
                 let (integer, fraction) = number_str.split_at(mid);
-                let integer = integer.parse::<u64>().unwrap();
+                let integer = match integer.parse::<u64>() {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                    }
+                };
                 let fraction = match fraction[1..].parse::<u64>() {
                     Ok(val) => val,
                     Err(_) => {
@@ -189,7 +209,12 @@ impl UnitParseUtil {
                 };
                 (integer, fraction)
             }
-            None => (number_str.parse::<u64>().unwrap(), 0),
+            None => match number_str.parse::<u64>() {
+                Ok(val) => (val, 0),
+                Err(_) => {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+            },
         };
 
         //从表中查找到后缀所对应的字节倍数
@@ -275,7 +300,8 @@ impl UnitParseUtil {
                 return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
             }
         };
-        let r = r.trim();
+        //split_at得到的右半部分以'-'开头，需跳过
+        let r = r[1..].trim();
         let r = match r.parse::<u32>() {
             Ok(val) => val,
             Err(_) => {
@@ -332,6 +358,301 @@ impl UnitParseUtil {
         return Ok(nice);
     }
 
+    /// @brief 解析资源限制
+    ///
+    /// 将传入的字符串解析为资源限制，格式为"soft:hard"或单个值(软硬限制相同)
+    /// "infinity"表示不限制，不同的资源使用不同的单位：
+    /// LimitCPU以秒为单位，LimitRTTIME以微秒为单位，二者均可带时间后缀；
+    /// 字节类的资源可带IEC后缀；LimitNICE带符号时表示nice值(-20~19)
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @param resource 资源类型
+    ///
+    /// @return 解析成功则返回Ok(ResourceLimit)，否则返回Err
+    pub fn parse_rlimit(s: &str, resource: Resource) -> Result<ResourceLimit, ParseError> {
+        let s = s.trim();
+        let (soft, hard) = match s.find(':') {
+            Some(idx) => (
+                Self::parse_rlimit_value(&s[..idx], resource)?,
+                Self::parse_rlimit_value(&s[idx + 1..], resource)?,
+            ),
+            None => {
+                let val = Self::parse_rlimit_value(s, resource)?;
+                (val, val)
+            }
+        };
+
+        // 软限制不能大于硬限制
+        if let (Some(soft), Some(hard)) = (soft, hard) {
+            if soft > hard {
+                return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+            }
+        }
+        if soft.is_none() && hard.is_some() {
+            return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+        }
+
+        Ok(ResourceLimit::new(soft, hard))
+    }
+
+    fn parse_rlimit_value(s: &str, resource: Resource) -> Result<Option<u64>, ParseError> {
+        let s = s.trim();
+        if s == "infinity" {
+            return Ok(None);
+        }
+        if s.is_empty() {
+            return Err(ParseError::new(
+                ParseErrorType::EUnexpectedEmpty,
+                String::new(),
+                0,
+            ));
+        }
+
+        let val = match resource {
+            // parse_sec返回纳秒
+            Resource::RLIMIT_CPU => Self::parse_sec(s)? / 1_000_000_000,
+            Resource::RLIMIT_RTTIME => {
+                if s.chars().all(|c| c.is_ascii_digit()) {
+                    Self::parse_rlimit_number(s)?
+                } else {
+                    Self::parse_sec(s)? / 1000
+                }
+            }
+            Resource::RLIMIT_AS
+            | Resource::RLIMIT_CORE
+            | Resource::RLIMIT_DATA
+            | Resource::RLIMIT_FSIZE
+            | Resource::RLIMIT_MEMLOCK
+            | Resource::RLIMIT_MSGQUEUE
+            | Resource::RLIMIT_RSS
+            | Resource::RLIMIT_STACK => Self::parse_size(s, SizeBase::IEC)?,
+            Resource::RLIMIT_NICE if s.starts_with('-') || s.starts_with('+') => {
+                // 带符号的值为nice值，转换为内核使用的 20 - nice
+                let nice = Self::parse_nice(s)?;
+                (20 - nice as i64) as u64
+            }
+            _ => Self::parse_rlimit_number(s)?,
+        };
+        Ok(Some(val))
+    }
+
+    fn parse_rlimit_number(s: &str) -> Result<u64, ParseError> {
+        match s.parse::<u64>() {
+            Ok(val) => Ok(val),
+            Err(_) => Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0)),
+        }
+    }
+
+    /// @brief 解析CPU调度优先级
+    ///
+    /// 将传入的字符串解析为CPUSchedulingPriority，范围为1~99
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(i32)，否则返回Err
+    pub fn parse_cpu_sched_priority(s: &str) -> Result<i32, ParseError> {
+        let priority = match s.trim().parse::<i32>() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+            }
+        };
+        if !(1..=99).contains(&priority) {
+            return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+        }
+        Ok(priority)
+    }
+
+    /// @brief 解析CPU列表
+    ///
+    /// 将传入的字符串解析为CPU编号的集合，各项之间以空格或逗号分隔，每一项可以是单个值或范围，如"0 2-3,5"，
+    /// CPU编号不能超过CPU_SET_SIZE
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回按升序排列的Ok(Vec<usize>)，否则返回Err
+    pub fn parse_cpu_set(s: &str) -> Result<Vec<usize>, ParseError> {
+        let mut cpus = BTreeSet::new();
+        for item in s.split(|c: char| c == ',' || c.is_whitespace()) {
+            if item.is_empty() {
+                continue;
+            }
+            let (l, r) = Self::parse_range(item)?;
+            if l > r || r >= CPU_SET_SIZE {
+                return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+            }
+            cpus.extend(l as usize..=r as usize);
+        }
+        Ok(cpus.into_iter().collect())
+    }
+
+    /// @brief 解析IO调度优先级
+    ///
+    /// 将传入的字符串解析为IOSchedulingPriority，范围为0~7
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(u8)，否则返回Err
+    pub fn parse_io_sched_priority(s: &str) -> Result<u8, ParseError> {
+        let priority = match s.trim().parse::<u8>() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+            }
+        };
+        if priority > 7 {
+            return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+        }
+        Ok(priority)
+    }
+
+    /// @brief 解析OOM（Out-of-Memory）分数调整值
+    ///
+    /// 将传入的字符串解析为OOMScoreAdjust，范围为-1000~1000
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(i32)，否则返回Err
+    pub fn parse_oom_score_adjust(s: &str) -> Result<i32, ParseError> {
+        let adjust = match s.trim().parse::<i32>() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+            }
+        };
+        if !(-1000..=1000).contains(&adjust) {
+            return Err(ParseError::new(ParseErrorType::ERANGE, String::new(), 0));
+        }
+        Ok(adjust)
+    }
+
+    /// @brief 解析信号
+    ///
+    /// 将传入的字符串解析为信号，支持"SIGTERM"、"TERM"以及信号编号
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(Signal)，否则返回Err
+    pub fn parse_signal(s: &str) -> Result<Signal, ParseError> {
+        let s = s.trim();
+        let ret = match s.parse::<i32>() {
+            Ok(num) => Signal::try_from(num),
+            Err(_) if s.starts_with("SIG") => Signal::from_str(s),
+            Err(_) => Signal::from_str(&format!("SIG{}", s)),
+        };
+        ret.map_err(|_| ParseError::new(ParseErrorType::EINVAL, String::new(), 0))
+    }
+
+    /// @brief 解析标准输入设置
+    ///
+    /// 将传入的字符串解析为StandardInput=对应的StdInput
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(StdInput)，否则返回Err
+    pub fn parse_std_input(s: &str) -> Result<StdInput, ParseError> {
+        let s = s.trim();
+        let input = match s {
+            "null" => StdInput::Null,
+            "tty" => StdInput::Tty,
+            "tty-force" => StdInput::TtyForce,
+            "socket" => StdInput::Socket,
+            "data" => StdInput::Data,
+            _ => match s.strip_prefix("file:") {
+                Some(path) if path.starts_with('/') => StdInput::File(path.to_string()),
+                _ => {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+            },
+        };
+        Ok(input)
+    }
+
+    /// @brief 解析标准输出设置
+    ///
+    /// 将传入的字符串解析为StandardOutput=/StandardError=对应的StdOutput
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(StdOutput)，否则返回Err
+    pub fn parse_std_output(s: &str) -> Result<StdOutput, ParseError> {
+        let s = s.trim();
+        let output = match s {
+            "inherit" => StdOutput::Inherit,
+            "null" => StdOutput::Null,
+            "tty" => StdOutput::Tty,
+            "journal" | "journal+console" | "kmsg" | "kmsg+console" => StdOutput::Journal,
+            "socket" => StdOutput::Socket,
+            _ => {
+                let (kind, path) = match s.find(':') {
+                    Some(idx) => (&s[..idx], &s[idx + 1..]),
+                    None => {
+                        return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                    }
+                };
+                if !path.starts_with('/') {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+                match kind {
+                    "file" => StdOutput::File(path.to_string()),
+                    "append" => StdOutput::Append(path.to_string()),
+                    "truncate" => StdOutput::Truncate(path.to_string()),
+                    _ => {
+                        return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                    }
+                }
+            }
+        };
+        Ok(output)
+    }
+
+    /// @brief 解析base64编码的数据
+    ///
+    /// 将传入的字符串按base64解码，忽略其中的空白字符
+    ///
+    /// @param s 需解析的字符串
+    ///
+    /// @return 解析成功则返回Ok(Vec<u8>)，否则返回Err
+    pub fn parse_base64(s: &str) -> Result<Vec<u8>, ParseError> {
+        let mut ret = Vec::new();
+        let mut buf: u32 = 0;
+        let mut bits = 0;
+        let mut padding = 0;
+        for c in s.bytes() {
+            let val = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                b'=' => {
+                    padding += 1;
+                    continue;
+                }
+                c if c.is_ascii_whitespace() => continue,
+                _ => {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+            };
+            // '='只能出现在末尾
+            if padding > 0 {
+                return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+            }
+            buf = (buf << 6) | val as u32;
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                ret.push((buf >> bits) as u8);
+                buf &= (1 << bits) - 1;
+            }
+        }
+        if padding > 2 {
+            return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+        }
+        Ok(ret)
+    }
+
     /// @brief 解析端口号
     ///
     /// 将传入的字符串解析为端口号
@@ -462,6 +783,49 @@ impl UnitParseUtil {
         return Ok(unit);
     }
 
+    /// @brief 通过Unit名加载被引用的Unit
+    ///
+    /// Unit在搜索路径中不存在或解析失败时，创建LoadState为NotFound或Error的占位Unit，
+    /// 引用它的Unit仍可以正常加载
+    ///
+    /// @param name 被引用的Unit名，不能为路径
+    ///
+    /// @return 成功则返回Unit的id，Unit名不合法时返回Err
+    pub fn load_unit(name: &str) -> Result<usize, ParseError> {
+        if !Self::is_valid_unit_name(name) {
+            return Err(ParseError::new(ParseErrorType::EINVAL, name.to_string(), 0));
+        }
+        if let Some(id) = UnitManager::get_id_with_path(name) {
+            return Ok(id);
+        }
+        if !UnitLookup::is_masked(name) && !UnitParser::unit_exists(name) {
+            return Ok(UnitParser::placeholder(
+                name,
+                LoadState::NotFound,
+                String::new(),
+            ));
+        }
+        match Self::parse_unit_no_type(name) {
+            Ok(id) => Ok(id),
+            Err(e) => {
+                eprintln!("Failed to load {}: {}", name, e.error_format());
+                Ok(UnitParser::placeholder(
+                    name,
+                    LoadState::Error,
+                    e.error_format(),
+                ))
+            }
+        }
+    }
+
+    /// @brief 判断是否为合法的Unit名，Unit名不能为路径且必须以已知的Unit类型为后缀
+    pub fn is_valid_unit_name(name: &str) -> bool {
+        !name.contains('/')
+            && name.rsplit_once('.').map_or(false, |(prefix, suffix)| {
+                !prefix.is_empty() && UNIT_SUFFIX.contains_key(suffix)
+            })
+    }
+
     pub fn parse_env(s: &str) -> Result<(String, String), ParseError> {
         let s = s.trim().split('=').collect::<Vec<&str>>();
         if s.len() != 2 {
@@ -721,3 +1085,160 @@ impl UnitParseUtil {
         return Ok(envs);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range_error() -> ParseError {
+        ParseError::new(ParseErrorType::ERANGE, String::new(), 0)
+    }
+
+    #[test]
+    fn parse_rlimit_soft_and_hard() {
+        let limit = UnitParseUtil::parse_rlimit("1024:4096", Resource::RLIMIT_NOFILE).unwrap();
+        assert_eq!(limit, ResourceLimit::new(Some(1024), Some(4096)));
+
+        // 单个值同时作为软限制与硬限制
+        let limit = UnitParseUtil::parse_rlimit("512", Resource::RLIMIT_NOFILE).unwrap();
+        assert_eq!(limit, ResourceLimit::new(Some(512), Some(512)));
+
+        let limit = UnitParseUtil::parse_rlimit("64:infinity", Resource::RLIMIT_NOFILE).unwrap();
+        assert_eq!(limit, ResourceLimit::new(Some(64), None));
+
+        let limit = UnitParseUtil::parse_rlimit("infinity", Resource::RLIMIT_NOFILE).unwrap();
+        assert_eq!(limit, ResourceLimit::new(None, None));
+    }
+
+    #[test]
+    fn parse_rlimit_units() {
+        let limit = UnitParseUtil::parse_rlimit("8M", Resource::RLIMIT_STACK).unwrap();
+        assert_eq!(limit.soft, Some(8 * 1024 * 1024));
+
+        let limit = UnitParseUtil::parse_rlimit("2min", Resource::RLIMIT_CPU).unwrap();
+        assert_eq!(limit.soft, Some(120));
+
+        // 不带单位的LimitRTTIME以微秒为单位
+        let limit = UnitParseUtil::parse_rlimit("500", Resource::RLIMIT_RTTIME).unwrap();
+        assert_eq!(limit.soft, Some(500));
+        let limit = UnitParseUtil::parse_rlimit("1s", Resource::RLIMIT_RTTIME).unwrap();
+        assert_eq!(limit.soft, Some(1_000_000));
+
+        // 带符号的LimitNICE为nice值
+        let limit = UnitParseUtil::parse_rlimit("-5", Resource::RLIMIT_NICE).unwrap();
+        assert_eq!(limit.soft, Some(25));
+        let limit = UnitParseUtil::parse_rlimit("10", Resource::RLIMIT_NICE).unwrap();
+        assert_eq!(limit.soft, Some(10));
+    }
+
+    #[test]
+    fn parse_rlimit_rejects_invalid() {
+        assert_eq!(
+            UnitParseUtil::parse_rlimit("4096:1024", Resource::RLIMIT_NOFILE),
+            Err(range_error())
+        );
+        assert_eq!(
+            UnitParseUtil::parse_rlimit("infinity:1024", Resource::RLIMIT_NOFILE),
+            Err(range_error())
+        );
+        assert!(UnitParseUtil::parse_rlimit("", Resource::RLIMIT_NOFILE).is_err());
+        assert!(UnitParseUtil::parse_rlimit("abc", Resource::RLIMIT_NOFILE).is_err());
+        assert!(UnitParseUtil::parse_rlimit("1:", Resource::RLIMIT_NOFILE).is_err());
+    }
+
+    #[test]
+    fn parse_cpu_set_sorts_and_merges() {
+        assert_eq!(
+            UnitParseUtil::parse_cpu_set("5 0,2-3 3-4").unwrap(),
+            vec![0, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            UnitParseUtil::parse_cpu_set("").unwrap(),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            UnitParseUtil::parse_cpu_set("1023").unwrap(),
+            vec![CPU_SET_SIZE as usize - 1]
+        );
+    }
+
+    #[test]
+    fn parse_cpu_set_rejects_out_of_range() {
+        assert_eq!(UnitParseUtil::parse_cpu_set("3-1"), Err(range_error()));
+        assert_eq!(UnitParseUtil::parse_cpu_set("1024"), Err(range_error()));
+        assert_eq!(
+            UnitParseUtil::parse_cpu_set("0-4294967295"),
+            Err(range_error())
+        );
+        assert!(UnitParseUtil::parse_cpu_set("a-b").is_err());
+    }
+
+    #[test]
+    fn parse_std_input_values() {
+        let parse = UnitParseUtil::parse_std_input;
+        assert_eq!(parse("null").unwrap(), StdInput::Null);
+        assert_eq!(parse(" tty ").unwrap(), StdInput::Tty);
+        assert_eq!(parse("tty-force").unwrap(), StdInput::TtyForce);
+        assert_eq!(parse("socket").unwrap(), StdInput::Socket);
+        assert_eq!(parse("data").unwrap(), StdInput::Data);
+        assert_eq!(
+            parse("file:/etc/motd").unwrap(),
+            StdInput::File("/etc/motd".to_string())
+        );
+        // 文件路径必须为绝对路径
+        assert!(parse("file:motd").is_err());
+        assert!(parse("file:").is_err());
+        assert!(parse("inherit").is_err());
+        assert!(parse("console").is_err());
+    }
+
+    #[test]
+    fn parse_std_output_values() {
+        let parse = UnitParseUtil::parse_std_output;
+        assert_eq!(parse("inherit").unwrap(), StdOutput::Inherit);
+        assert_eq!(parse("null").unwrap(), StdOutput::Null);
+        assert_eq!(parse("tty").unwrap(), StdOutput::Tty);
+        assert_eq!(parse("socket").unwrap(), StdOutput::Socket);
+        for journal in ["journal", "journal+console", "kmsg", "kmsg+console"] {
+            assert_eq!(parse(journal).unwrap(), StdOutput::Journal);
+        }
+        assert_eq!(
+            parse("file:/var/log/a").unwrap(),
+            StdOutput::File("/var/log/a".to_string())
+        );
+        assert_eq!(
+            parse("append:/var/log/a").unwrap(),
+            StdOutput::Append("/var/log/a".to_string())
+        );
+        assert_eq!(
+            parse("truncate:/var/log/a").unwrap(),
+            StdOutput::Truncate("/var/log/a".to_string())
+        );
+        assert!(parse("file:log").is_err());
+        assert!(parse("pipe:/var/log/a").is_err());
+        assert!(parse("console").is_err());
+    }
+
+    #[test]
+    fn parse_base64_decodes() {
+        let parse = UnitParseUtil::parse_base64;
+        assert_eq!(parse("").unwrap(), b"");
+        assert_eq!(parse("Zg==").unwrap(), b"f");
+        assert_eq!(parse("Zm8=").unwrap(), b"fo");
+        assert_eq!(parse("Zm9v").unwrap(), b"foo");
+        assert_eq!(parse("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(parse("aGVsbG8gd29ybGQK").unwrap(), b"hello world\n");
+        // 忽略空白字符，多行的StandardInputData=拼接在一起
+        assert_eq!(parse(" Zm9v\nYmFy ").unwrap(), b"foobar");
+        assert_eq!(parse("+/8=").unwrap(), vec![0xfb, 0xff]);
+    }
+
+    #[test]
+    fn parse_base64_rejects_invalid() {
+        let parse = UnitParseUtil::parse_base64;
+        assert!(parse("Zm9v!").is_err());
+        assert!(parse("Zg==Zg").is_err());
+        assert!(parse("Z===").is_err());
+        assert!(parse("Zm9v-_").is_err());
+    }
+}
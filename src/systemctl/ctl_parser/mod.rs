@@ -104,7 +104,7 @@ pub enum Pattern {
     Global,
     Runtime,
     Force,
-    PresetMode,
+    PresetMode(String),
     Root(String),
     Lines(i32),
     Output(String),
@@ -143,7 +143,7 @@ impl ToString for Pattern {
             Pattern::Global => "global".to_string(),
             Pattern::Runtime => "runtime".to_string(),
             Pattern::Force => "force".to_string(),
-            Pattern::PresetMode => "preset-mode".to_string(),
+            Pattern::PresetMode(s) => format!("preset-mode={}", s),
             Pattern::Root(s) => format!("{}={}", "root".to_string(), s),
             Pattern::Lines(i) => format!("{}={}", "lines".to_string(), i),
             Pattern::Output(s) => format!("{}={}", "output".to_string(), s),
@@ -161,7 +161,7 @@ lazy_static! {
         map.insert("list-timers", CommandOperation::ListTimers);
         map.insert("start", CommandOperation::Start);
         map.insert("stop", CommandOperation::Stop);
-        map.insert("reload", CommandOperation::UnSupported);
+        map.insert("reload", CommandOperation::Reload);
         map.insert("restart", CommandOperation::Restart);
         map.insert("try-restart", CommandOperation::TryRestart);
         map.insert("reload-or-restart", CommandOperation::ReloadOrRestart);
@@ -251,7 +251,7 @@ lazy_static! {
         map.insert("global", Pattern::Global);
         map.insert("runtime", Pattern::Runtime);
         map.insert("force", Pattern::Force);
-        map.insert("preset-mode", Pattern::PresetMode);
+        map.insert("preset-mode", Pattern::PresetMode(String::new()));
         map.insert("root", Pattern::Root(String::new()));
         map.insert("lines", Pattern::Lines(-1));
         map.insert("output", Pattern::Output(String::new()));
@@ -293,9 +293,13 @@ impl CtlParser {
             | CommandOperation::Stop
             | CommandOperation::TryRestart
             | CommandOperation::Reload
+            | CommandOperation::Cancel
             | CommandOperation::AddRequires
             | CommandOperation::AddWants
             | CommandOperation::Kill
+            | CommandOperation::Show
+            | CommandOperation::Status
+            | CommandOperation::Cat
             | CommandOperation::ListDependencies
             | CommandOperation::Enable
             | CommandOperation::Disable
@@ -352,6 +356,7 @@ impl CtlParser {
                         todo!()
                     }
                     Pattern::JobMode(_) => Pattern::JobMode(words[1].to_string()),
+                    Pattern::PresetMode(_) => Pattern::PresetMode(words[1].to_string()),
                     Pattern::KillWho(_) => Pattern::KillWho(words[1].to_string()),
                     Pattern::Lines(_) => match words[1].parse::<i32>() {
                         Ok(val) => Pattern::Lines(val),
@@ -2,20 +2,11 @@ use super::ctl_parser::{CommandOperation, CtlParser, Pattern};
 use super::{ctl_path, DRAGON_REACH_CTL_PIPE};
 use crate::error::ErrorFormat;
 use crate::manager::ctl_manager::CtlManager;
-use lazy_static::lazy_static;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::os::fd::FromRawFd;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::os::unix::fs::FileTypeExt;
 
-lazy_static! {
-    static ref CTL_READER: Mutex<Arc<File>> = {
-        let file = Systemctl::init_listener();
-        Mutex::new(Arc::new(file))
-    };
-}
 #[derive(Debug)]
 pub struct Command {
     pub(crate) operation: CommandOperation,
@@ -54,43 +45,53 @@ impl Systemctl {
     /// # 初始化监听器 - 初始化系统服务控制命令监听器
     ///
     /// 打开系统服务控制命令的管道文件描述符，并设置为非阻塞模式。
+    /// 以读写方式打开，使管道始终存在写端，避免没有systemctl时epoll持续报告EPOLLHUP。
     ///
     pub fn init_listener() -> File {
-        let fd = unsafe { libc::open(ctl_path().as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK) };
+        let fd = unsafe {
+            libc::open(
+                ctl_path().as_ptr(),
+                libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
         if fd < 0 {
             panic!("open ctl pipe error");
         }
         unsafe { File::from_raw_fd(fd) }
     }
-    /// # 监听控制命令 - 监听系统服务控制命令
+
+    /// # 处理控制命令 - 读取并执行管道中的系统服务控制命令
     ///
-    /// 持续从系统服务控制管道中读取命令。
+    /// 在主循环中管道可读时调用，读取当前管道中的全部数据。
     ///
-    pub fn ctl_listen() {
-        println!("ctl listen");
-        let mut guard = CTL_READER.lock().unwrap();
-        let mut s = String::new();
+    pub fn handle_ctl(reader: &mut File) {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 512];
         loop {
-            s.clear();
-            match guard.read_to_string(&mut s) {
-                Ok(size) if size > 0 => match CtlParser::parse_ctl(&s) {
-                    Ok(cmd) => {
-                        let _ = CtlManager::exec_ctl(cmd);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to parse command: {}", e.error_format());
-                    }
-                },
-                Ok(_) => {
-                    // 如果读取到的大小为0，说明没有数据可读，适当休眠
-                    thread::sleep(Duration::from_millis(100));
-                }
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(size) => buf.extend_from_slice(&chunk[..size]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
                 Err(e) => {
                     eprintln!("Failed to read from pipe: {}", e);
-                    break;
+                    return;
                 }
             }
         }
+        if buf.is_empty() {
+            return;
+        }
+
+        let s = String::from_utf8_lossy(&buf);
+        match CtlParser::parse_ctl(&s) {
+            Ok(cmd) => {
+                let _ = CtlManager::exec_ctl(cmd);
+            }
+            Err(e) => {
+                eprintln!("Failed to parse command: {}", e.error_format());
+            }
+        }
     }
 
     /// # 检查控制管道是否存在 - 检查系统服务控制管道文件是否存在
@@ -99,7 +100,7 @@ impl Systemctl {
     ///
     fn is_ctl_exists() -> bool {
         if let Ok(metadata) = fs::metadata(DRAGON_REACH_CTL_PIPE) {
-            metadata.is_file()
+            metadata.file_type().is_fifo()
         } else {
             false
         }
@@ -107,6 +108,8 @@ impl Systemctl {
 
     fn init_ctl_pipe() {
         if !Self::is_ctl_exists() {
+            // 路径被其他类型的文件占用时将其删除后重新创建管道
+            let _ = fs::remove_file(DRAGON_REACH_CTL_PIPE);
             let path = ctl_path();
             let ret = unsafe { libc::mkfifo(path.as_ptr(), 0o666) };
             if ret != 0 {
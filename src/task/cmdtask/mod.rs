@@ -1,8 +1,15 @@
+use std::os::unix::process::CommandExt;
 use std::process::Command;
 
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
+};
+
 use crate::{
     error::runtime_error::{RuntimeError, RuntimeErrorType},
-    manager::UnitManager,
+    manager::process_manager::{ProcessManager, ProcessOwner},
+    task::exec_context::ExecContext,
 };
 
 #[derive(Debug, Clone, Default)]
@@ -13,54 +20,62 @@ pub struct CmdTask {
     pub dir: String,
     pub envs: Vec<(String, String)>,
     pub pid: u32,
+    pub context: ExecContext,
+    //该命令所属Unit的id
+    pub unit: usize,
 }
 
 impl CmdTask {
-    /// ## 以新建进程的方式运行这个cmd
-    pub fn spawn(&self) -> Result<(), RuntimeError> {
-        let result = Command::new(&self.path)
-            .args(&self.cmd)
-            .current_dir(self.dir.clone())
-            .envs(self.envs.clone())
-            .spawn();
-        match result {
-            Ok(proc) => {
-                UnitManager::push_cmd_proc(proc);
-            }
-            Err(err) => {
-                if !self.ignore {
-                    eprintln!("{}: Command failed: {}", self.path, err);
-                    return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
-                }
-            }
-        }
-        Ok(())
+    /// ## 构造该cmd对应的Command，并在子进程exec之前应用执行上下文
+    ///
+    /// 用于服务的主进程，打开StandardInput=/StandardOutput=等指定的文件失败时返回Err
+    pub fn command(&self) -> std::io::Result<Command> {
+        self.build_command(self.context.clone())
+    }
+
+    /// ## 构造作为控制命令运行的Command
+    ///
+    /// 控制命令不获取服务的终端，见StdioConfig::for_control
+    fn control_command(&self) -> std::io::Result<Command> {
+        let mut context = self.context.clone();
+        *context.stdio_mut() = context.stdio().for_control();
+        self.build_command(context)
     }
 
-    /// ## 阻塞式运行
-    pub fn no_spawn(&self) -> Result<(), RuntimeError> {
-        let result = Command::new(&self.path)
+    fn build_command(&self, context: ExecContext) -> std::io::Result<Command> {
+        let mut command = Command::new(&self.path);
+        let (stdin, stdout, stderr) = context.stdio().open()?;
+        command
             .args(&self.cmd)
             .current_dir(self.dir.clone())
             .envs(self.envs.clone())
-            .spawn();
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr);
+
+        // 需要分配内存的数据在fork之前准备好
+        let limits = context.effective_limits();
+        let cpu_set = context.cpu_set();
+        unsafe {
+            command.pre_exec(move || context.apply(&limits, cpu_set.as_ref()));
+        }
+        Ok(command)
+    }
 
+    /// ## 以新建进程的方式运行这个cmd，作为所属Unit的控制进程
+    ///
+    /// 创建成功后记录其pid，其退出由主循环回收后分发给所属Unit。
+    /// 创建失败且未设置忽略错误时返回Err，忽略错误时pid保持为0
+    pub fn spawn(&mut self) -> Result<(), RuntimeError> {
+        self.pid = 0;
+        let result = self.control_command().and_then(|mut command| {
+            ProcessManager::spawn(&mut command, ProcessOwner::Control(self.unit))
+        });
         match result {
-            Ok(mut child) => match child.wait() {
-                Ok(status) => {
-                    if !status.success() && !self.ignore {
-                        return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
-                    }
-                }
-                Err(_) => {
-                    if !self.ignore {
-                        return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
-                    }
-                }
-            },
-            Err(e) => {
+            Ok(child) => self.pid = child.id(),
+            Err(err) => {
+                eprintln!("{}: Command failed: {}", self.path, err);
                 if !self.ignore {
-                    eprintln!("{}: Command failed: {}", self.path, e);
                     return Err(RuntimeError::new(RuntimeErrorType::ExecFailed));
                 }
             }
@@ -69,20 +84,13 @@ impl CmdTask {
     }
 
     /// ## 若这个cmd任务spawn了，则kill这个cmd进程
+    ///
+    /// cmd进程为独立进程组的首进程，其派生的进程一并终止
     pub fn stop(&mut self) {
         if self.pid != 0 {
-            let res = UnitManager::pop_cmd_proc(self.pid).unwrap();
-
-            let mut proc = res.lock().unwrap();
-
-            match proc.try_wait() {
-                //进程正常退出
-                Ok(Some(_status)) => {}
-                //进程错误退出(或启动失败)
-                _ => {
-                    proc.kill().expect("Cannot kill cmd task");
-                }
-            };
+            // 进程可能已经退出并被回收，忽略错误
+            let _ = killpg(Pid::from_raw(self.pid as i32), Signal::SIGKILL);
+            self.pid = 0;
         }
     }
 }
@@ -0,0 +1,400 @@
+use std::fmt;
+
+use nix::sched::{sched_setaffinity, CpuSet};
+use nix::sys::resource::{setrlimit, Resource};
+use nix::unistd::Pid;
+
+use crate::manager::manager_config::ManagerConfig;
+use crate::parse::RLIMIT_TABLE;
+use crate::task::stdio::StdioConfig;
+
+/// 资源限制，对应setrlimit中的软限制与硬限制，None表示infinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceLimit {
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+impl ResourceLimit {
+    pub fn new(soft: Option<u64>, hard: Option<u64>) -> Self {
+        ResourceLimit { soft, hard }
+    }
+}
+
+/// CPU调度策略，对应CPUSchedulingPolicy=
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSchedPolicy {
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    RoundRobin,
+}
+
+impl CpuSchedPolicy {
+    fn as_raw(&self) -> libc::c_int {
+        match self {
+            CpuSchedPolicy::Other => libc::SCHED_OTHER,
+            CpuSchedPolicy::Batch => libc::SCHED_BATCH,
+            CpuSchedPolicy::Idle => libc::SCHED_IDLE,
+            CpuSchedPolicy::Fifo => libc::SCHED_FIFO,
+            CpuSchedPolicy::RoundRobin => libc::SCHED_RR,
+        }
+    }
+
+    /// 只有实时调度策略可以设置非0的优先级
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, CpuSchedPolicy::Fifo | CpuSchedPolicy::RoundRobin)
+    }
+}
+
+impl fmt::Display for CpuSchedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CpuSchedPolicy::Other => "other",
+            CpuSchedPolicy::Batch => "batch",
+            CpuSchedPolicy::Idle => "idle",
+            CpuSchedPolicy::Fifo => "fifo",
+            CpuSchedPolicy::RoundRobin => "rr",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// IO调度类型，对应IOSchedulingClass=
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoSchedClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoSchedClass {
+    fn as_raw(&self) -> libc::c_int {
+        match self {
+            IoSchedClass::Realtime => 1,
+            IoSchedClass::BestEffort => 2,
+            IoSchedClass::Idle => 3,
+        }
+    }
+}
+
+impl fmt::Display for IoSchedClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            IoSchedClass::Realtime => "realtime",
+            IoSchedClass::BestEffort => "best-effort",
+            IoSchedClass::Idle => "idle",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// ioprio_set相关常量，libc中未定义
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+// 只设置IOSchedulingClass=时使用的默认优先级
+const IOPRIO_DEFAULT_PRIORITY: u8 = 4;
+
+/// 进程执行上下文，记录在子进程exec之前需要应用的各项设置
+#[derive(Debug, Clone, Default)]
+pub struct ExecContext {
+    // Limit*= 设置的资源限制
+    limits: Vec<(Resource, ResourceLimit)>,
+    // Nice=
+    nice: Option<i8>,
+    // CPUSchedulingPolicy=
+    cpu_sched_policy: Option<CpuSchedPolicy>,
+    // CPUSchedulingPriority=
+    cpu_sched_priority: Option<i32>,
+    // CPUAffinity=，为空表示不设置
+    cpu_affinity: Vec<usize>,
+    // IOSchedulingClass=
+    io_sched_class: Option<IoSchedClass>,
+    // IOSchedulingPriority=
+    io_sched_priority: Option<u8>,
+    // OOMScoreAdjust=
+    oom_score_adjust: Option<i32>,
+    // UMask=，未设置时使用0022
+    umask: Option<u32>,
+    // StandardInput=/StandardOutput=/StandardError=/TTYPath=
+    stdio: StdioConfig,
+}
+
+impl ExecContext {
+    /// ## 设置资源限制，重复设置同一资源时后者覆盖前者
+    pub fn set_limit(&mut self, resource: Resource, limit: ResourceLimit) {
+        match self.limits.iter_mut().find(|(r, _)| *r == resource) {
+            Some(item) => item.1 = limit,
+            None => self.limits.push((resource, limit)),
+        }
+    }
+
+    /// ## 获取实际生效的资源限制
+    ///
+    /// Unit中未设置的资源限制继承管理器的DefaultLimit*=设置
+    pub fn effective_limits(&self) -> Vec<(Resource, ResourceLimit)> {
+        let mut ret = ManagerConfig::default_limits();
+        for (resource, limit) in &self.limits {
+            match ret.iter_mut().find(|(r, _)| r == resource) {
+                Some(item) => item.1 = *limit,
+                None => ret.push((*resource, *limit)),
+            }
+        }
+        ret
+    }
+
+    pub fn set_nice(&mut self, nice: i8) {
+        self.nice = Some(nice);
+    }
+
+    pub fn nice(&self) -> Option<i8> {
+        self.nice
+    }
+
+    pub fn set_cpu_sched_policy(&mut self, policy: CpuSchedPolicy) {
+        self.cpu_sched_policy = Some(policy);
+    }
+
+    pub fn set_cpu_sched_priority(&mut self, priority: i32) {
+        self.cpu_sched_priority = Some(priority);
+    }
+
+    /// ## 添加可运行的CPU，CPUAffinity=为空值时清空之前的设置
+    pub fn extend_cpu_affinity(&mut self, cpus: &[usize]) {
+        if cpus.is_empty() {
+            self.cpu_affinity.clear();
+            return;
+        }
+        for cpu in cpus {
+            if !self.cpu_affinity.contains(cpu) {
+                self.cpu_affinity.push(*cpu);
+            }
+        }
+        self.cpu_affinity.sort_unstable();
+    }
+
+    pub fn set_io_sched_class(&mut self, class: IoSchedClass) {
+        self.io_sched_class = Some(class);
+    }
+
+    pub fn set_io_sched_priority(&mut self, priority: u8) {
+        self.io_sched_priority = Some(priority);
+    }
+
+    pub fn set_oom_score_adjust(&mut self, adjust: i32) {
+        self.oom_score_adjust = Some(adjust);
+    }
+
+    pub fn set_umask(&mut self, umask: u32) {
+        self.umask = Some(umask);
+    }
+
+    pub fn stdio(&self) -> &StdioConfig {
+        &self.stdio
+    }
+
+    pub fn stdio_mut(&mut self) -> &mut StdioConfig {
+        &mut self.stdio
+    }
+
+    /// ## 构造CPUAffinity=对应的CpuSet，需在fork之前调用
+    pub fn cpu_set(&self) -> Option<CpuSet> {
+        if self.cpu_affinity.is_empty() {
+            return None;
+        }
+        let mut set = CpuSet::new();
+        for cpu in &self.cpu_affinity {
+            // 超出CpuSet容量的CPU忽略
+            let _ = set.set(*cpu);
+        }
+        Some(set)
+    }
+
+    /// ## 执行上下文的属性，用于show命令输出
+    pub fn properties(&self) -> Vec<(String, String)> {
+        let mut props = vec![
+            (
+                "UMask".to_string(),
+                format!("{:04o}", self.umask.unwrap_or(0o022)),
+            ),
+            ("Nice".to_string(), self.nice.unwrap_or(0).to_string()),
+            (
+                "CPUSchedulingPolicy".to_string(),
+                self.cpu_sched_policy
+                    .unwrap_or(CpuSchedPolicy::Other)
+                    .to_string(),
+            ),
+            (
+                "CPUSchedulingPriority".to_string(),
+                self.cpu_sched_priority.unwrap_or(0).to_string(),
+            ),
+            (
+                "CPUAffinity".to_string(),
+                self.cpu_affinity
+                    .iter()
+                    .map(|cpu| cpu.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            (
+                "IOSchedulingClass".to_string(),
+                self.io_sched_class
+                    .unwrap_or(IoSchedClass::BestEffort)
+                    .to_string(),
+            ),
+            (
+                "IOSchedulingPriority".to_string(),
+                self.io_sched_priority
+                    .unwrap_or(IOPRIO_DEFAULT_PRIORITY)
+                    .to_string(),
+            ),
+            (
+                "OOMScoreAdjust".to_string(),
+                self.oom_score_adjust.unwrap_or(0).to_string(),
+            ),
+            ("StandardInput".to_string(), self.stdio.input.to_string()),
+            (
+                "StandardOutput".to_string(),
+                self.stdio.output().to_string(),
+            ),
+            ("StandardError".to_string(), self.stdio.error.to_string()),
+            ("TTYPath".to_string(), self.stdio.tty_path().to_string()),
+            ("TTYReset".to_string(), yes_no(self.stdio.tty_reset)),
+            ("TTYVHangup".to_string(), yes_no(self.stdio.tty_vhangup)),
+            (
+                "TTYVTDisallocate".to_string(),
+                yes_no(self.stdio.tty_vt_disallocate),
+            ),
+        ];
+
+        // 资源限制按照systemd的格式输出，LimitXXX为硬限制，LimitXXXSoft为软限制
+        let limits = self.effective_limits();
+        let mut names = RLIMIT_TABLE.iter().collect::<Vec<_>>();
+        names.sort_by_key(|(name, _)| **name);
+        for (name, resource) in names {
+            let limit = match limits.iter().find(|(r, _)| r == resource) {
+                Some((_, limit)) => *limit,
+                None => continue,
+            };
+            let format_val = |val: Option<u64>| match val {
+                Some(val) => val.to_string(),
+                None => "infinity".to_string(),
+            };
+            props.push((name.to_string(), format_val(limit.hard)));
+            props.push((format!("{}Soft", name), format_val(limit.soft)));
+        }
+        props
+    }
+
+    /// ## 在子进程中应用执行上下文
+    ///
+    /// 该函数在fork之后、exec之前调用，只能调用异步信号安全的函数，
+    /// 所以资源限制与CpuSet等需要分配内存的数据应在fork之前准备好
+    pub fn apply(
+        &self,
+        limits: &[(Resource, ResourceLimit)],
+        cpu_set: Option<&CpuSet>,
+    ) -> std::io::Result<()> {
+        // 每个进程都成为新会话及进程组的首进程，以便停止时找到其派生的所有进程
+        if unsafe { libc::setsid() } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.stdio.acquire_controlling_tty()?;
+
+        unsafe {
+            libc::umask(self.umask.unwrap_or(0o022) as libc::mode_t);
+        }
+
+        // 降低OOMScoreAdjust需要特权，应在其他设置之前完成
+        if let Some(adjust) = self.oom_score_adjust {
+            Self::write_oom_score_adjust(adjust)?;
+        }
+
+        if let Some(nice) = self.nice {
+            let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice as libc::c_int) };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        if self.io_sched_class.is_some() || self.io_sched_priority.is_some() {
+            let class = self.io_sched_class.unwrap_or(IoSchedClass::BestEffort);
+            let priority = match class {
+                IoSchedClass::Idle => 0,
+                _ => self.io_sched_priority.unwrap_or(IOPRIO_DEFAULT_PRIORITY),
+            };
+            let ioprio = (class.as_raw() << IOPRIO_CLASS_SHIFT) | priority as libc::c_int;
+            let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        if self.cpu_sched_policy.is_some() || self.cpu_sched_priority.is_some() {
+            let policy = self.cpu_sched_policy.unwrap_or(CpuSchedPolicy::Other);
+            let param = libc::sched_param {
+                sched_priority: if policy.is_realtime() {
+                    self.cpu_sched_priority.unwrap_or(1)
+                } else {
+                    0
+                },
+            };
+            let ret = unsafe { libc::sched_setscheduler(0, policy.as_raw(), &param) };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        if let Some(cpu_set) = cpu_set {
+            if let Err(e) = sched_setaffinity(Pid::from_raw(0), cpu_set) {
+                return Err(std::io::Error::from_raw_os_error(e as i32));
+            }
+        }
+
+        // 资源限制最后设置，避免LimitNICE等影响前面的设置
+        for (resource, limit) in limits {
+            if let Err(e) = setrlimit(*resource, limit.soft, limit.hard) {
+                return Err(std::io::Error::from_raw_os_error(e as i32));
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入/proc/self/oom_score_adj，不进行内存分配
+    fn write_oom_score_adjust(adjust: i32) -> std::io::Result<()> {
+        // 将adjust格式化到栈上的缓冲区
+        let mut buf = [0u8; 12];
+        let mut pos = buf.len();
+        let mut val = (adjust as i64).unsigned_abs();
+        loop {
+            pos -= 1;
+            buf[pos] = b'0' + (val % 10) as u8;
+            val /= 10;
+            if val == 0 {
+                break;
+            }
+        }
+        if adjust < 0 {
+            pos -= 1;
+            buf[pos] = b'-';
+        }
+
+        let path = b"/proc/self/oom_score_adj\0";
+        let fd = unsafe { libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let len = buf.len() - pos;
+        let ret = unsafe { libc::write(fd, buf[pos..].as_ptr() as *const libc::c_void, len) };
+        unsafe { libc::close(fd) };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+fn yes_no(b: bool) -> String {
+    if b { "yes" } else { "no" }.to_string()
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use nix::sys::signal::Signal;
+
+/// 停止服务时向哪些进程发送信号，对应KillMode=
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KillMode {
+    /// 向服务的所有进程发送信号
+    #[default]
+    ControlGroup,
+    /// KillSignal=只发送给主进程，SIGKILL发送给所有进程
+    Mixed,
+    /// 只向主进程发送信号
+    Process,
+    /// 不发送任何信号
+    None,
+}
+
+impl fmt::Display for KillMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            KillMode::ControlGroup => "control-group",
+            KillMode::Mixed => "mixed",
+            KillMode::Process => "process",
+            KillMode::None => "none",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 停止服务的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillPhase {
+    /// 发送KillSignal=，请求进程退出
+    Terminate,
+    /// 超时后发送SIGKILL
+    Kill,
+}
+
+/// 服务的停止方式设置
+#[derive(Debug, Clone, Copy)]
+pub struct KillContext {
+    pub kill_mode: KillMode,
+    pub kill_signal: Signal,
+    pub send_sigkill: bool,
+}
+
+impl Default for KillContext {
+    fn default() -> Self {
+        KillContext {
+            kill_mode: KillMode::default(),
+            kill_signal: Signal::SIGTERM,
+            send_sigkill: true,
+        }
+    }
+}
+
+impl KillContext {
+    /// ## 获取某一阶段需要发送的信号
+    ///
+    /// 返回信号及是否发送给服务的所有进程，该阶段不需要发送信号时返回None
+    pub fn signal(&self, phase: KillPhase) -> Option<(Signal, bool)> {
+        match (phase, self.kill_mode) {
+            (_, KillMode::None) => None,
+            (KillPhase::Terminate, mode) => {
+                Some((self.kill_signal, mode == KillMode::ControlGroup))
+            }
+            (KillPhase::Kill, _) if !self.send_sigkill => None,
+            (KillPhase::Kill, mode) => Some((Signal::SIGKILL, mode != KillMode::Process)),
+        }
+    }
+
+    pub fn properties(&self) -> Vec<(String, String)> {
+        vec![
+            ("KillMode".to_string(), self.kill_mode.to_string()),
+            (
+                "KillSignal".to_string(),
+                (self.kill_signal as i32).to_string(),
+            ),
+            (
+                "SendSIGKILL".to_string(),
+                if self.send_sigkill { "yes" } else { "no" }.to_string(),
+            ),
+        ]
+    }
+}
@@ -1 +1,4 @@
 pub mod cmdtask;
+pub mod exec_context;
+pub mod kill_context;
+pub mod stdio;
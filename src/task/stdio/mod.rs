@@ -0,0 +1,371 @@
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::Stdio;
+
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use nix::unistd::getpid;
+
+/// 默认的TTYPath
+pub const DEFAULT_TTY_PATH: &str = "/dev/console";
+
+/// linux/vt.h中的VT_DISALLOCATE
+const VT_DISALLOCATE: libc::c_ulong = 0x5608;
+
+/// 标准输入的来源，对应StandardInput=
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StdInput {
+    /// 未设置时继承DragonReach自身的标准输入
+    #[default]
+    Inherit,
+    Null,
+    Tty,
+    /// 与tty相同，但即使终端被其他会话控制也强制获取
+    TtyForce,
+    File(String),
+    Socket,
+    /// 使用StandardInputData=/StandardInputText=设置的数据
+    Data,
+}
+
+impl fmt::Display for StdInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StdInput::Inherit => write!(f, "inherit"),
+            StdInput::Null => write!(f, "null"),
+            StdInput::Tty => write!(f, "tty"),
+            StdInput::TtyForce => write!(f, "tty-force"),
+            StdInput::File(path) => write!(f, "file:{}", path),
+            StdInput::Socket => write!(f, "socket"),
+            StdInput::Data => write!(f, "data"),
+        }
+    }
+}
+
+/// 标准输出/标准错误的去向，对应StandardOutput=/StandardError=
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StdOutput {
+    /// StandardOutput=inherit在标准输入为终端时复制标准输入，StandardError=inherit复制标准输出
+    Inherit,
+    Null,
+    Tty,
+    /// DragonReach暂无日志服务，journal/kmsg均输出到DragonReach自身的标准输出
+    Journal,
+    File(String),
+    Append(String),
+    Truncate(String),
+    Socket,
+}
+
+impl fmt::Display for StdOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StdOutput::Inherit => write!(f, "inherit"),
+            StdOutput::Null => write!(f, "null"),
+            StdOutput::Tty => write!(f, "tty"),
+            StdOutput::Journal => write!(f, "journal"),
+            StdOutput::File(path) => write!(f, "file:{}", path),
+            StdOutput::Append(path) => write!(f, "append:{}", path),
+            StdOutput::Truncate(path) => write!(f, "truncate:{}", path),
+            StdOutput::Socket => write!(f, "socket"),
+        }
+    }
+}
+
+/// 解析后的文件描述符来源
+enum StdioTarget {
+    /// 继承DragonReach自身的文件描述符
+    Manager,
+    Null,
+    File(File),
+}
+
+impl StdioTarget {
+    fn try_clone(&self) -> io::Result<StdioTarget> {
+        match self {
+            StdioTarget::Manager => Ok(StdioTarget::Manager),
+            StdioTarget::Null => Ok(StdioTarget::Null),
+            StdioTarget::File(file) => Ok(StdioTarget::File(file.try_clone()?)),
+        }
+    }
+
+    fn into_stdio(self) -> Stdio {
+        match self {
+            StdioTarget::Manager => Stdio::inherit(),
+            StdioTarget::Null => Stdio::null(),
+            StdioTarget::File(file) => Stdio::from(file),
+        }
+    }
+}
+
+/// 服务进程的标准输入输出设置
+#[derive(Debug, Clone)]
+pub struct StdioConfig {
+    pub input: StdInput,
+    /// None表示未设置，实际值由output()决定
+    pub output: Option<StdOutput>,
+    pub error: StdOutput,
+    pub tty_path: Option<String>,
+    /// TTYReset=，服务启动前及退出后重置终端
+    pub tty_reset: bool,
+    /// TTYVHangup=，服务启动前及退出后挂断所有打开该终端的进程
+    pub tty_vhangup: bool,
+    /// TTYVTDisallocate=，服务启动前及退出后释放虚拟终端
+    pub tty_vt_disallocate: bool,
+    /// StandardInputData=/StandardInputText=设置的数据
+    pub input_data: Vec<u8>,
+}
+
+impl Default for StdioConfig {
+    fn default() -> Self {
+        StdioConfig {
+            input: StdInput::Inherit,
+            output: None,
+            error: StdOutput::Inherit,
+            tty_path: None,
+            tty_reset: false,
+            tty_vhangup: false,
+            tty_vt_disallocate: false,
+            input_data: Vec::new(),
+        }
+    }
+}
+
+impl StdioConfig {
+    pub fn tty_path(&self) -> &str {
+        self.tty_path.as_deref().unwrap_or(DEFAULT_TTY_PATH)
+    }
+
+    /// 未设置StandardOutput=时，标准输入为终端则继承标准输入，否则输出到journal
+    pub fn output(&self) -> StdOutput {
+        match &self.output {
+            Some(output) => output.clone(),
+            None if self.is_tty_input() => StdOutput::Inherit,
+            None => StdOutput::Journal,
+        }
+    }
+
+    /// 标准输入为终端时，服务成为该终端的控制进程
+    pub fn is_tty_input(&self) -> bool {
+        matches!(self.input, StdInput::Tty | StdInput::TtyForce)
+    }
+
+    /// ## 控制命令使用的标准输入输出设置
+    ///
+    /// 终端只属于服务的主进程，ExecStartPre=、ExecStop=等控制命令的标准输入为/dev/null，
+    /// 输出仍写入主进程使用的终端，但不会将其设为控制终端
+    pub fn for_control(&self) -> StdioConfig {
+        let mut stdio = self.clone();
+        if self.is_tty_input() {
+            stdio.input = StdInput::Null;
+            stdio.output = Some(match self.output() {
+                StdOutput::Inherit => StdOutput::Tty,
+                other => other,
+            });
+        }
+        stdio
+    }
+
+    /// ## 打开标准输入、标准输出、标准错误对应的文件
+    ///
+    /// 需在fork之前调用，返回的Stdio直接交给Command
+    pub fn open(&self) -> io::Result<(Stdio, Stdio, Stdio)> {
+        let input = match &self.input {
+            StdInput::Inherit => StdioTarget::Manager,
+            StdInput::Null => StdioTarget::Null,
+            StdInput::Tty | StdInput::TtyForce => {
+                StdioTarget::File(Self::open_tty(self.tty_path())?)
+            }
+            StdInput::File(path) => StdioTarget::File(
+                OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NOCTTY)
+                    .open(path)?,
+            ),
+            StdInput::Socket => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "StandardInput=socket requires socket activation",
+                ));
+            }
+            StdInput::Data => StdioTarget::File(Self::open_data(&self.input_data)?),
+        };
+
+        // StandardOutput=inherit只在标准输入为终端时复制标准输入，
+        // 标准输入为只读文件或数据时使用DragonReach自身的标准输出，作为1号进程时为/dev/null
+        let output = match self.output() {
+            StdOutput::Inherit => match &self.input {
+                StdInput::Inherit | StdInput::Tty | StdInput::TtyForce => input.try_clone()?,
+                _ if getpid().as_raw() == 1 => StdioTarget::Null,
+                _ => StdioTarget::Manager,
+            },
+            other => self.open_output(&other)?,
+        };
+        // StandardError=inherit复制标准输出
+        let error = match &self.error {
+            StdOutput::Inherit => output.try_clone()?,
+            other => self.open_output(other)?,
+        };
+
+        Ok((input.into_stdio(), output.into_stdio(), error.into_stdio()))
+    }
+
+    fn open_output(&self, output: &StdOutput) -> io::Result<StdioTarget> {
+        let mut options = OpenOptions::new();
+        options.write(true).custom_flags(libc::O_NOCTTY).mode(0o644);
+        let target = match output {
+            StdOutput::Inherit | StdOutput::Journal => StdioTarget::Manager,
+            StdOutput::Null => StdioTarget::Null,
+            StdOutput::Tty => StdioTarget::File(Self::open_tty(self.tty_path())?),
+            StdOutput::File(path) => StdioTarget::File(options.create(true).open(path)?),
+            StdOutput::Append(path) => {
+                StdioTarget::File(options.create(true).append(true).open(path)?)
+            }
+            StdOutput::Truncate(path) => {
+                StdioTarget::File(options.create(true).truncate(true).open(path)?)
+            }
+            StdOutput::Socket => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "StandardOutput=socket requires socket activation",
+                ));
+            }
+        };
+        Ok(target)
+    }
+
+    /// ## 将标准输入的终端设为服务进程的控制终端
+    ///
+    /// 在子进程成为会话首进程后、exec之前调用，此时标准输入已经重定向到终端
+    pub fn acquire_controlling_tty(&self) -> io::Result<()> {
+        if !self.is_tty_input() {
+            return Ok(());
+        }
+        let force = (self.input == StdInput::TtyForce) as libc::c_int;
+        if unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, force) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// ## 按TTYVHangup=、TTYVTDisallocate=、TTYReset=的设置处理服务使用的终端
+    ///
+    /// 在服务启动前及退出后调用，终端不存在等错误会被忽略
+    pub fn reset_tty(&self) {
+        if !self.tty_reset && !self.tty_vhangup && !self.tty_vt_disallocate {
+            return;
+        }
+        let path = self.tty_path();
+        if self.tty_vhangup {
+            if let Ok(tty) = Self::open_tty(path) {
+                unsafe {
+                    libc::ioctl(tty.as_raw_fd(), libc::TIOCVHANGUP);
+                }
+            }
+        }
+        if self.tty_vt_disallocate {
+            Self::vt_disallocate(path);
+        }
+        if self.tty_reset {
+            let _ = Self::reset_terminal(path);
+        }
+    }
+
+    /// 释放/dev/ttyN对应的虚拟终端，终端仍在使用(例如当前前台终端)时改为清屏
+    fn vt_disallocate(path: &str) {
+        let vt = match path
+            .strip_prefix("/dev/tty")
+            .and_then(|n| n.parse::<libc::c_int>().ok())
+        {
+            Some(vt) if vt > 0 => vt,
+            _ => return,
+        };
+        if let Ok(tty0) = Self::open_tty("/dev/tty0") {
+            let ret = unsafe { libc::ioctl(tty0.as_raw_fd(), VT_DISALLOCATE, vt) };
+            if ret >= 0 {
+                return;
+            }
+        }
+        if let Ok(mut tty) = Self::open_tty(path) {
+            // 光标移至左上角并清除屏幕及回滚缓冲
+            let _ = tty.write_all(b"\x1b[r\x1b[H\x1b[3J\x1b[2J");
+        }
+    }
+
+    /// 将终端恢复为默认的行规程设置
+    fn reset_terminal(path: &str) -> io::Result<()> {
+        let mut tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+        let fd = tty.as_raw_fd();
+
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_iflag &=
+            !(libc::IGNBRK | libc::BRKINT | libc::ISTRIP | libc::INLCR | libc::IGNCR | libc::IUCLC);
+        termios.c_iflag |= libc::ICRNL | libc::IMAXBEL | libc::IUTF8;
+        termios.c_oflag |= libc::ONLCR | libc::OPOST;
+        termios.c_cflag |= libc::CREAD;
+        termios.c_lflag = libc::ISIG
+            | libc::ICANON
+            | libc::IEXTEN
+            | libc::ECHO
+            | libc::ECHOE
+            | libc::ECHOK
+            | libc::ECHOCTL
+            | libc::ECHOKE;
+        termios.c_cc[libc::VINTR] = 0o3;
+        termios.c_cc[libc::VQUIT] = 0o34;
+        termios.c_cc[libc::VERASE] = 0o177;
+        termios.c_cc[libc::VKILL] = 0o25;
+        termios.c_cc[libc::VEOF] = 0o4;
+        termios.c_cc[libc::VSTART] = 0o21;
+        termios.c_cc[libc::VSTOP] = 0o23;
+        termios.c_cc[libc::VSUSP] = 0o32;
+        termios.c_cc[libc::VLNEXT] = 0o26;
+        termios.c_cc[libc::VWERASE] = 0o27;
+        termios.c_cc[libc::VREPRINT] = 0o22;
+        termios.c_cc[libc::VEOL] = 0;
+        termios.c_cc[libc::VEOL2] = 0;
+        termios.c_cc[libc::VTIME] = 0;
+        termios.c_cc[libc::VMIN] = 1;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // 发送RIS序列重置终端状态，并丢弃未处理的输入输出
+        let _ = tty.write_all(b"\x1bc");
+        unsafe {
+            libc::tcflush(fd, libc::TCIOFLUSH);
+        }
+        Ok(())
+    }
+
+    fn open_tty(path: &str) -> io::Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)
+    }
+
+    /// 将StandardInputData=的数据写入memfd，作为服务的标准输入
+    fn open_data(data: &[u8]) -> io::Result<File> {
+        let name = CString::new("dragonreach-stdin").unwrap();
+        let fd = match memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC) {
+            Ok(fd) => fd,
+            Err(e) => return Err(io::Error::from_raw_os_error(e as i32)),
+        };
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(data)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+}
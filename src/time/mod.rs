@@ -1,2 +1,36 @@
 pub mod calandar;
 pub mod timer;
+
+use std::time::{Duration, Instant};
+
+use nix::time::{clock_gettime, ClockId};
+
+/// ## 当前CLOCK_MONOTONIC时间
+fn monotonic_now() -> Duration {
+    let now = clock_gettime(ClockId::CLOCK_MONOTONIC).unwrap();
+    Duration::new(now.tv_sec() as u64, now.tv_nsec() as u32)
+}
+
+/// ## 将Instant转换为CLOCK_MONOTONIC的微秒数
+///
+/// CLOCK_MONOTONIC在exec前后保持不变，daemon-reexec时以此序列化时间点
+pub fn instant_to_monotonic_usec(instant: Instant) -> u64 {
+    let (now, mono) = (Instant::now(), monotonic_now());
+    let mono = if instant >= now {
+        mono + (instant - now)
+    } else {
+        mono.saturating_sub(now - instant)
+    };
+    mono.as_micros() as u64
+}
+
+/// ## 将CLOCK_MONOTONIC的微秒数转换为Instant
+pub fn monotonic_usec_to_instant(usec: u64) -> Instant {
+    let (now, mono) = (Instant::now(), monotonic_now());
+    let target = Duration::from_micros(usec);
+    if target >= mono {
+        now + (target - mono)
+    } else {
+        now.checked_sub(mono - target).unwrap_or(now)
+    }
+}
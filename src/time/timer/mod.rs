@@ -32,19 +32,24 @@ impl Timer {
         }
     }
 
-    /// ## 判断当前是否到时，若执行该函数时已到时，将会执行定时任务
+    /// ## 判断当前是否到时
     ///
     /// ### return 到时返回true,否则返回false
-    pub fn check(&mut self) -> bool {
-        // println!("{},{}",self.instant.elapsed().as_micros(),self.duration.as_micros());
-        if self.instant.elapsed().saturating_sub(self.duration) > Duration::ZERO {
-            // TODO: 未进行错误处理
-            if let Err(_e) = (self.callback)() {
-                println!("task error");
-            }
-            return true;
+    pub fn is_expired(&self) -> bool {
+        self.instant.elapsed().saturating_sub(self.duration) > Duration::ZERO
+    }
+
+    /// ## 执行定时任务
+    pub fn run(&mut self) {
+        // TODO: 未进行错误处理
+        if let Err(_e) = (self.callback)() {
+            println!("task error");
         }
-        return false;
+    }
+
+    /// ## 获取此计时器的到期时间
+    pub fn deadline(&self) -> Instant {
+        self.instant + self.duration
     }
 
     /// ## 获取此计时器的拥有者Unit
@@ -5,17 +5,24 @@ use std::sync::atomic::Ordering;
 
 use crate::error::parse_error::ParseError;
 use crate::error::parse_error::ParseErrorType;
-use crate::error::runtime_error::RuntimeError;
+use crate::error::runtime_error::{RuntimeError, RuntimeErrorType};
 
 use crate::executor::ExitStatus;
+use crate::manager::process_manager::ProcessExit;
+use crate::manager::UnitManager;
 use crate::parse::parse_util::UnitParseUtil;
-use crate::parse::Segment;
+use crate::parse::{Segment, UnitParser};
+use nix::unistd::Pid;
 
 pub mod service;
 pub mod signal;
 pub mod target;
 pub mod timer;
-use self::target::TargetUnit;
+
+/// 默认依赖中系统初始化完成的目标
+pub const SYSINIT_TARGET: &str = "sysinit.target";
+/// 默认依赖中关机的目标
+pub const SHUTDOWN_TARGET: &str = "shutdown.target";
 
 pub fn generate_unit_id() -> usize {
     static UNIT_ID: AtomicUsize = AtomicUsize::new(1);
@@ -75,15 +82,6 @@ pub trait Unit: Sync + Send + Debug {
     /// ### return OK(())/Err
     fn run(&mut self) -> Result<(), RuntimeError>;
 
-    /// ## 设置unit_id
-    ///
-    /// ### return OK(())/Err
-    fn set_unit_id(&mut self) -> usize {
-        let ret = generate_unit_id();
-        self.unit_base_mut().set_id(ret);
-        ret
-    }
-
     /// ## Unit退出后逻辑
     ///
     /// 一般只有可运行的Unit(如Service)需要重写此函数
@@ -105,6 +103,61 @@ pub trait Unit: Sync + Send + Debug {
     fn restart(&mut self) -> Result<(), RuntimeError> {
         unimplemented!()
     }
+
+    /// ## Unit重新加载配置的逻辑
+    ///
+    /// 只有可运行的Unit(如Service)支持重新加载
+    fn reload(&mut self) -> Result<(), RuntimeError> {
+        Err(RuntimeError::new(RuntimeErrorType::UnsupportedOperation))
+    }
+
+    /// ## Unit的控制进程退出后逻辑
+    ///
+    /// 控制进程的退出被分发后才能执行下一个控制命令，一般只有可运行的Unit(如Service)需要重写此函数
+    fn control_exit(&mut self, _pid: Pid, _exit: ProcessExit) {}
+
+    /// ## 添加隐式依赖
+    ///
+    /// 在Unit文件解析完成、初始化之前调用，各类型Unit按照自身配置添加依赖
+    fn add_implicit_dependencies(&mut self) -> Result<(), ParseError> {
+        Ok(())
+    }
+
+    /// ## 获取Unit的属性列表，用于show命令输出
+    ///
+    /// 默认只包含BaseUnit的属性，各Unit可重写此函数追加自己的属性
+    fn properties(&self) -> Vec<(String, String)> {
+        self.unit_base().properties()
+    }
+
+    /// ## 序列化Unit的运行状态，用于daemon-reexec后恢复
+    ///
+    /// 默认只包含BaseUnit的状态，各Unit可重写此函数追加自己的状态
+    fn serialize(&self) -> Vec<(String, String)> {
+        self.unit_base().serialize()
+    }
+
+    /// ## 恢复一项daemon-reexec之前序列化的状态，未知的项被忽略
+    fn deserialize_item(&mut self, key: &str, value: &str) {
+        self.unit_base_mut().deserialize_item(key, value);
+    }
+
+    /// ## 所有状态恢复完毕后接管daemon-reexec之前仍在运行的进程及定时任务
+    fn coldplug(&mut self) {}
+
+    /// ## daemon-reload时从旧配置的Unit继承运行状态
+    ///
+    /// 默认只继承BaseUnit的状态，各Unit可重写此函数继承自己的运行状态
+    fn reload_from(&mut self, old: &dyn Unit) {
+        self.unit_base_mut().inherit_state(old.unit_base());
+    }
+
+    /// ## 将对from的依赖替换为对to的依赖，用于以别名的提供者替换占位Unit
+    ///
+    /// 默认只替换BaseUnit中的依赖，各Unit可重写此函数替换自己引用的Unit
+    fn replace_dependency(&mut self, from: usize, to: usize) {
+        self.unit_base_mut().replace_dependency(from, to);
+    }
 }
 
 //Unit状态
@@ -120,6 +173,23 @@ pub enum UnitState {
     Maintenance,
 }
 
+impl UnitState {
+    const ALL: &'static [UnitState] = &[
+        UnitState::Active,
+        UnitState::Inactive,
+        UnitState::Activating,
+        UnitState::Deactivating,
+        UnitState::Failed,
+        UnitState::Reloading,
+        UnitState::Maintenance,
+    ];
+
+    /// 由to_string的结果获得状态
+    pub fn from_name(name: &str) -> Option<UnitState> {
+        Self::ALL.iter().copied().find(|s| s.to_string() == name)
+    }
+}
+
 impl ToString for UnitState {
     fn to_string(&self) -> String {
         match *self {
@@ -141,10 +211,13 @@ pub enum UnitSubState {
     Waiting,
     StartPre,
     StartPost,
+    Reload,
+    Stop,
     StopSigterm,
     StopSigkill,
     StopFinalSigterm,
     StopFinalSigkill,
+    StopPost,
     Dead,
     AutoRestart,
     Failed,
@@ -154,6 +227,34 @@ pub enum UnitSubState {
     Unknown,
 }
 
+impl UnitSubState {
+    const ALL: &'static [UnitSubState] = &[
+        UnitSubState::Running,
+        UnitSubState::Waiting,
+        UnitSubState::StartPre,
+        UnitSubState::StartPost,
+        UnitSubState::Reload,
+        UnitSubState::Stop,
+        UnitSubState::StopSigterm,
+        UnitSubState::StopSigkill,
+        UnitSubState::StopFinalSigterm,
+        UnitSubState::StopFinalSigkill,
+        UnitSubState::StopPost,
+        UnitSubState::Dead,
+        UnitSubState::AutoRestart,
+        UnitSubState::Failed,
+        UnitSubState::Activating,
+        UnitSubState::Deactivating,
+        UnitSubState::Plugged,
+        UnitSubState::Unknown,
+    ];
+
+    /// 由to_string的结果获得状态
+    pub fn from_name(name: &str) -> Option<UnitSubState> {
+        Self::ALL.iter().copied().find(|s| s.to_string() == name)
+    }
+}
+
 impl ToString for UnitSubState {
     fn to_string(&self) -> String {
         match *self {
@@ -161,10 +262,13 @@ impl ToString for UnitSubState {
             UnitSubState::Waiting => "waiting".to_string(),
             UnitSubState::StartPre => "start-pre".to_string(),
             UnitSubState::StartPost => "start-post".to_string(),
+            UnitSubState::Reload => "reload".to_string(),
+            UnitSubState::Stop => "stop".to_string(),
             UnitSubState::StopSigterm => "stop-sigterm".to_string(),
             UnitSubState::StopSigkill => "stop-sigkill".to_string(),
             UnitSubState::StopFinalSigterm => "stop-final-sigterm".to_string(),
             UnitSubState::StopFinalSigkill => "stop-final-sigkill".to_string(),
+            UnitSubState::StopPost => "stop-post".to_string(),
             UnitSubState::Dead => "dead".to_string(),
             UnitSubState::AutoRestart => "auto-restart".to_string(),
             UnitSubState::Failed => "failed".to_string(),
@@ -191,7 +295,7 @@ impl ToString for LoadState {
             LoadState::Loaded => "loaded".to_string(),
             LoadState::NotFound => "not found".to_string(),
             LoadState::Error => "error".to_string(),
-            LoadState::Masked => "maksed".to_string(),
+            LoadState::Masked => "masked".to_string(),
         }
     }
 }
@@ -227,6 +331,10 @@ pub struct BaseUnit {
     load_state: LoadState,
     unit_type: UnitType,
     unit_id: usize,
+    // 加载该Unit的文件路径，内置Unit为空
+    fragment_path: String,
+    // LoadState为Error时的解析错误
+    load_error: String,
 }
 
 impl Default for BaseUnit {
@@ -240,6 +348,8 @@ impl Default for BaseUnit {
             load_state: LoadState::Loaded,
             unit_type: UnitType::Unknown,
             unit_id: 0,
+            fragment_path: String::new(),
+            load_error: String::new(),
         }
     }
 }
@@ -290,6 +400,38 @@ impl BaseUnit {
         &self.state
     }
 
+    pub fn sub_state(&self) -> &UnitSubState {
+        &self.sub_state
+    }
+
+    pub fn set_sub_state(&mut self, sub_state: UnitSubState) {
+        self.sub_state = sub_state;
+    }
+
+    pub fn load_state(&self) -> &LoadState {
+        &self.load_state
+    }
+
+    pub fn set_load_state(&mut self, load_state: LoadState) {
+        self.load_state = load_state;
+    }
+
+    pub fn load_error(&self) -> &str {
+        &self.load_error
+    }
+
+    pub fn set_load_error(&mut self, load_error: String) {
+        self.load_error = load_error;
+    }
+
+    pub fn fragment_path(&self) -> &str {
+        &self.fragment_path
+    }
+
+    pub fn set_fragment_path(&mut self, fragment_path: String) {
+        self.fragment_path = fragment_path;
+    }
+
     pub fn unit_type(&self) -> &UnitType {
         &self.unit_type
     }
@@ -302,6 +444,128 @@ impl BaseUnit {
         self.unit_name.clone()
     }
 
+    /// ## Unit基本属性，用于show命令输出
+    pub fn properties(&self) -> Vec<(String, String)> {
+        vec![
+            ("Id".to_string(), self.unit_name.clone()),
+            (
+                "Description".to_string(),
+                self.unit_part.description.clone(),
+            ),
+            ("LoadState".to_string(), self.load_state.to_string()),
+            ("LoadError".to_string(), self.load_error.clone()),
+            ("FragmentPath".to_string(), self.fragment_path.clone()),
+            ("ActiveState".to_string(), self.state.to_string()),
+            ("SubState".to_string(), self.sub_state.to_string()),
+            (
+                "Requires".to_string(),
+                self.dep_names(self.unit_part.requires()),
+            ),
+            (
+                "Requisite".to_string(),
+                self.dep_names(self.unit_part.requisite()),
+            ),
+            ("Wants".to_string(), self.dep_names(self.unit_part.wants())),
+            (
+                "BindsTo".to_string(),
+                self.dep_names(self.unit_part.binds_to()),
+            ),
+            (
+                "PartOf".to_string(),
+                self.dep_names(self.unit_part.part_of()),
+            ),
+            (
+                "RequiredBy".to_string(),
+                self.dep_names(self.unit_part.required_by()),
+            ),
+            (
+                "WantedBy".to_string(),
+                self.dep_names(self.unit_part.wanted_by()),
+            ),
+            (
+                "BoundBy".to_string(),
+                self.dep_names(self.unit_part.be_binded_by()),
+            ),
+            (
+                "ConsistsOf".to_string(),
+                self.dep_names(self.unit_part.consists_of()),
+            ),
+            (
+                "Conflicts".to_string(),
+                self.dep_names(self.unit_part.conflicts()),
+            ),
+            (
+                "ConflictedBy".to_string(),
+                self.dep_names(self.unit_part.conflicted_by()),
+            ),
+            (
+                "Before".to_string(),
+                self.dep_names(self.unit_part.before()),
+            ),
+            ("After".to_string(), self.dep_names(self.unit_part.after())),
+            (
+                "DefaultDependencies".to_string(),
+                if self.unit_part.default_dependencies {
+                    "yes"
+                } else {
+                    "no"
+                }
+                .to_string(),
+            ),
+        ]
+    }
+
+    /// ## 继承重新加载之前的运行状态
+    pub fn inherit_state(&mut self, old: &BaseUnit) {
+        self.state = old.state;
+        self.sub_state = old.sub_state;
+    }
+
+    pub fn replace_dependency(&mut self, from: usize, to: usize) {
+        self.unit_part.replace_dependency(from, to);
+        self.install_part.replace_dependency(from, to);
+    }
+
+    /// ## Unit的运行状态，用于daemon-reexec
+    pub fn serialize(&self) -> Vec<(String, String)> {
+        vec![
+            ("state".to_string(), self.state.to_string()),
+            ("sub-state".to_string(), self.sub_state.to_string()),
+        ]
+    }
+
+    pub fn deserialize_item(&mut self, key: &str, value: &str) {
+        match key {
+            "state" => {
+                if let Some(state) = UnitState::from_name(value) {
+                    self.state = state;
+                }
+            }
+            "sub-state" => {
+                if let Some(sub_state) = UnitSubState::from_name(value) {
+                    self.sub_state = sub_state;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 将依赖项的id转换为以空格分隔的Unit名，调用时当前Unit已被锁住，故不再查询自身
+    fn dep_names(&self, ids: &[usize]) -> String {
+        ids.iter()
+            .map(|id| {
+                if *id == self.unit_id {
+                    return self.unit_name.clone();
+                }
+                match UnitManager::get_unit_with_id(id) {
+                    Some(unit) => unit.lock().unwrap().unit_base().unit_name(),
+                    None => id.to_string(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
     /// ## Unit基本格式化信息
     pub fn unit_info(&self) -> String {
         format!(
@@ -336,6 +600,8 @@ pub struct UnitPart {
     requires: Vec<usize>,
     // 依赖项，同时与当前Unit启动，不需要考虑其成功与否
     wants: Vec<usize>,
+    // 依赖项，与requires类似，但不会启动这些Unit，启动当前Unit时它们必须已经处于运行状态
+    requisite: Vec<usize>,
     // 该Unit在下列Units启动完成之后才能启动
     after: Vec<usize>,
     // after相反的语义
@@ -348,8 +614,19 @@ pub struct UnitPart {
     on_failure: Vec<usize>,
     // 与当前Unit冲突项
     conflicts: Vec<usize>,
-    // 与当前Unit绑定的项，当前Unit失败或者重启时这些项也会跟着终止或重启
+    // 是否添加默认依赖
+    default_dependencies: bool,
+    // 以下为加载后由其他Unit的依赖反向推导出的依赖项
+    // Requires=或RequiredBy=当前Unit的项(RequiredBy)，当前Unit停止时这些项也会停止
+    required_by: Vec<usize>,
+    // Wants=或WantedBy=当前Unit的项(WantedBy)
+    wanted_by: Vec<usize>,
+    // BindsTo=当前Unit的项(BoundBy)，当前Unit失败或者重启时这些项也会跟着终止或重启
     be_binded_by: Vec<usize>,
+    // PartOf=当前Unit的项(ConsistsOf)，当前Unit停止或重启时这些项也会跟着停止或重启
+    consists_of: Vec<usize>,
+    // Conflicts=当前Unit的项(ConflictedBy)
+    conflicted_by: Vec<usize>,
 }
 
 impl Default for UnitPart {
@@ -359,13 +636,19 @@ impl Default for UnitPart {
             documentation: Vec::new(),
             requires: Vec::new(),
             wants: Vec::new(),
+            requisite: Vec::new(),
             after: Vec::new(),
             before: Vec::new(),
             binds_to: Vec::new(),
             part_of: Vec::new(),
             on_failure: Vec::new(),
             conflicts: Vec::new(),
+            default_dependencies: true,
+            required_by: Vec::new(),
+            wanted_by: Vec::new(),
             be_binded_by: Vec::new(),
+            consists_of: Vec::new(),
+            conflicted_by: Vec::new(),
         }
     }
 }
@@ -389,66 +672,75 @@ impl UnitPart {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入requires列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.requires
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.requires.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::Wants => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.wants
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.wants.push(UnitParseUtil::load_unit(unit_path)?);
+                }
+            }
+            BaseUnitAttr::Requisite => {
+                let units = val.split_whitespace().collect::<Vec<&str>>();
+                for unit_path in units {
+                    self.requisite.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::After => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.after
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.after.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::Before => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.before
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.before.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::BindsTo => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.binds_to
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.binds_to.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::PartOf => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.part_of
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.part_of.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::OnFailure => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    self.on_failure
-                        .push(UnitParseUtil::parse_unit_no_type(unit_path)?);
+                    self.on_failure.push(UnitParseUtil::load_unit(unit_path)?);
                 }
             }
             BaseUnitAttr::Conflicts => {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    let unit = UnitParseUtil::parse_unit_no_type(unit_path)?;
+                    let unit = UnitParseUtil::load_unit(unit_path)?;
                     self.conflicts.push(unit);
                 }
             }
+            BaseUnitAttr::DefaultDependencies => {
+                self.default_dependencies = UnitParseUtil::parse_boolean(val)?
+            }
+            // 没有mount类型的Unit可以依赖，只检查格式后忽略
+            BaseUnitAttr::RequiresMountsFor => {
+                if val.split_whitespace().any(|path| !path.starts_with('/')) {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+                eprintln!("RequiresMountsFor= is not supported, ignoring: {}", val);
+            }
         }
         return Ok(());
     }
@@ -469,6 +761,10 @@ impl UnitPart {
         &self.wants
     }
 
+    pub fn requisite(&self) -> &[usize] {
+        &self.requisite
+    }
+
     pub fn after(&self) -> &[usize] {
         &self.after
     }
@@ -493,19 +789,126 @@ impl UnitPart {
         &self.conflicts
     }
 
+    pub fn default_dependencies(&self) -> bool {
+        self.default_dependencies
+    }
+
+    /// ## 添加隐式依赖，依赖的Unit不存在时忽略
+    pub fn add_implicit_dependency(
+        &mut self,
+        attr: &BaseUnitAttr,
+        unit: &str,
+    ) -> Result<(), ParseError> {
+        if !UnitParser::unit_exists(unit) {
+            return Ok(());
+        }
+        let id = UnitParseUtil::load_unit(unit)?;
+        let list = match attr {
+            BaseUnitAttr::Requires => &mut self.requires,
+            BaseUnitAttr::After => &mut self.after,
+            BaseUnitAttr::Before => &mut self.before,
+            BaseUnitAttr::Conflicts => &mut self.conflicts,
+            _ => return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0)),
+        };
+        Self::push_unique(list, id);
+        Ok(())
+    }
+
+    /// ## 添加默认依赖
+    ///
+    /// DefaultDependencies=yes时，在sysinit.target之后启动，并在关机时停止
+    pub fn add_default_dependencies(&mut self) -> Result<(), ParseError> {
+        if !self.default_dependencies {
+            return Ok(());
+        }
+        self.add_implicit_dependency(&BaseUnitAttr::Requires, SYSINIT_TARGET)?;
+        self.add_implicit_dependency(&BaseUnitAttr::After, SYSINIT_TARGET)?;
+        self.add_implicit_dependency(&BaseUnitAttr::Conflicts, SHUTDOWN_TARGET)?;
+        self.add_implicit_dependency(&BaseUnitAttr::Before, SHUTDOWN_TARGET)?;
+        Ok(())
+    }
+
+    pub fn required_by(&self) -> &[usize] {
+        &self.required_by
+    }
+
+    pub fn wanted_by(&self) -> &[usize] {
+        &self.wanted_by
+    }
+
     pub fn be_binded_by(&self) -> &[usize] {
         &self.be_binded_by
     }
 
+    pub fn consists_of(&self) -> &[usize] {
+        &self.consists_of
+    }
+
+    pub fn conflicted_by(&self) -> &[usize] {
+        &self.conflicted_by
+    }
+
+    pub fn push_requires_unit(&mut self, id: usize) {
+        Self::push_unique(&mut self.requires, id);
+    }
+
+    pub fn push_wants_unit(&mut self, id: usize) {
+        Self::push_unique(&mut self.wants, id);
+    }
+
+    pub fn push_after_unit(&mut self, id: usize) {
+        Self::push_unique(&mut self.after, id);
+    }
+
+    pub fn push_before_unit(&mut self, id: usize) {
+        Self::push_unique(&mut self.before, id);
+    }
+
+    pub fn push_required_by(&mut self, id: usize) {
+        Self::push_unique(&mut self.required_by, id);
+    }
+
+    pub fn push_wanted_by(&mut self, id: usize) {
+        Self::push_unique(&mut self.wanted_by, id);
+    }
+
     pub fn push_be_binded_by(&mut self, id: usize) {
-        if !self.be_binded_by.contains(&id) {
-            self.be_binded_by.push(id);
+        Self::push_unique(&mut self.be_binded_by, id);
+    }
+
+    pub fn push_consists_of(&mut self, id: usize) {
+        Self::push_unique(&mut self.consists_of, id);
+    }
+
+    pub fn push_conflicted_by(&mut self, id: usize) {
+        Self::push_unique(&mut self.conflicted_by, id);
+    }
+
+    /// 反向依赖可能被多次推导，已存在时不重复添加
+    fn push_unique(list: &mut Vec<usize>, id: usize) {
+        if !list.contains(&id) {
+            list.push(id);
         }
     }
 
-    pub fn push_after_unit(&mut self, id: usize) {
-        if !self.after.contains(&id) {
-            self.after.push(id);
+    pub fn replace_dependency(&mut self, from: usize, to: usize) {
+        for list in [
+            &mut self.requires,
+            &mut self.wants,
+            &mut self.requisite,
+            &mut self.after,
+            &mut self.before,
+            &mut self.binds_to,
+            &mut self.part_of,
+            &mut self.on_failure,
+            &mut self.conflicts,
+            &mut self.required_by,
+            &mut self.wanted_by,
+            &mut self.be_binded_by,
+            &mut self.consists_of,
+            &mut self.conflicted_by,
+        ] {
+            replace_id(list, from, to);
         }
     }
 }
@@ -516,7 +919,8 @@ pub struct InstallPart {
     wanted_by: Vec<usize>,
     requires_by: Vec<usize>,
     also: Vec<usize>,
-    alias: String,
+    // 该Unit的别名
+    alias: Vec<String>,
 }
 
 impl Default for InstallPart {
@@ -525,7 +929,7 @@ impl Default for InstallPart {
             wanted_by: Vec::new(),
             requires_by: Vec::new(),
             also: Vec::new(),
-            alias: String::new(),
+            alias: Vec::new(),
         }
     }
 }
@@ -538,7 +942,7 @@ impl InstallPart {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    let unit = UnitParseUtil::parse_unit::<TargetUnit>(unit_path)?;
+                    let unit = UnitParseUtil::load_unit(unit_path)?;
                     self.requires_by.push(unit);
                 }
             }
@@ -546,7 +950,7 @@ impl InstallPart {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    let unit = UnitParseUtil::parse_unit_no_type(unit_path)?;
+                    let unit = UnitParseUtil::load_unit(unit_path)?;
                     self.also.push(unit);
                 }
             }
@@ -554,12 +958,17 @@ impl InstallPart {
                 let units = val.split_whitespace().collect::<Vec<&str>>();
                 //TODO:目前先加入列表，可能会出现循环依赖问题，后续应解决循环依赖问题
                 for unit_path in units {
-                    let unit = UnitParseUtil::parse_unit::<TargetUnit>(unit_path)?;
+                    let unit = UnitParseUtil::load_unit(unit_path)?;
                     self.wanted_by.push(unit);
                 }
             }
             InstallUnitAttr::Alias => {
-                self.alias = String::from(val);
+                for name in val.split_whitespace() {
+                    if !UnitParseUtil::is_valid_unit_name(name) {
+                        return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                    }
+                    self.alias.push(name.to_string());
+                }
             }
             InstallUnitAttr::None => {
                 return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
@@ -580,9 +989,29 @@ impl InstallPart {
         &self.also
     }
 
-    pub fn alias(&self) -> &str {
+    pub fn alias(&self) -> &[String] {
         &self.alias
     }
+
+    pub fn replace_dependency(&mut self, from: usize, to: usize) {
+        for list in [&mut self.wanted_by, &mut self.requires_by, &mut self.also] {
+            replace_id(list, from, to);
+        }
+    }
+}
+
+/// 将列表中的from替换为to，to已在列表中时直接移除from
+fn replace_id(list: &mut Vec<usize>, from: usize, to: usize) {
+    if !list.contains(&from) {
+        return;
+    }
+    if list.contains(&to) {
+        list.retain(|id| *id != from);
+    } else {
+        list.iter_mut()
+            .filter(|id| **id == from)
+            .for_each(|id| *id = to);
+    }
 }
 //对应Unit文件的各种属性
 #[allow(dead_code)]
@@ -598,6 +1027,8 @@ pub enum BaseUnitAttr {
     Requires,
     //这个 Unit 启动时，触发启动列出的每个 Unit 模块，而不去考虑这些模板启动是否成功
     Wants,
+    //与Requires类似，但不会启动列出的模块，启动当前Unit时这些模块必须已经处于运行状态，否则启动失败
+    Requisite,
     //后面列出的所有模块全部启动完成以后，才会启动当前的服务
     After,
     //在启动指定的任务一个模块之间，都会首先确证当前服务已经运行
@@ -610,6 +1041,10 @@ pub enum BaseUnitAttr {
     OnFailure,
     //与这个模块有冲突的模块，如果列出的模块中有已经在运行的，这个服务就不能启动，反之亦然
     Conflicts,
+    //是否添加默认依赖，默认为yes
+    DefaultDependencies,
+    //列出的路径所在的文件系统需在当前模块启动前挂载
+    RequiresMountsFor,
 }
 
 #[allow(dead_code)]
@@ -625,3 +1060,42 @@ pub enum InstallUnitAttr {
     //别名
     Alias,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_state_round_trip() {
+        for state in UnitState::ALL {
+            assert_eq!(UnitState::from_name(&state.to_string()), Some(*state));
+        }
+        assert_eq!(UnitState::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn unit_sub_state_round_trip() {
+        for sub_state in UnitSubState::ALL {
+            assert_eq!(
+                UnitSubState::from_name(&sub_state.to_string()),
+                Some(*sub_state)
+            );
+        }
+        assert_eq!(UnitSubState::from_name(""), None);
+    }
+
+    #[test]
+    fn base_unit_round_trip() {
+        let base = BaseUnit {
+            state: UnitState::Deactivating,
+            sub_state: UnitSubState::StopSigterm,
+            ..Default::default()
+        };
+        let mut restored = BaseUnit::default();
+        for (key, value) in base.serialize() {
+            restored.deserialize_item(&key, &value);
+        }
+        assert_eq!(restored.state, UnitState::Deactivating);
+        assert_eq!(restored.sub_state, UnitSubState::StopSigterm);
+    }
+}
@@ -1,18 +1,26 @@
-use super::{BaseUnit, Unit};
+use super::{BaseUnit, Unit, UnitSubState};
 use crate::error::runtime_error::RuntimeError;
 use crate::error::{parse_error::ParseError, parse_error::ParseErrorType};
 use crate::executor::service_executor::ServiceExecutor;
 use crate::executor::ExitStatus;
+use crate::manager::process_manager::{ProcessExit, ProcessManager};
+use crate::manager::UnitManager;
 
 use crate::parse::parse_service::ServiceParser;
 use crate::parse::parse_util::UnitParseUtil;
 use crate::parse::{Segment, SERVICE_UNIT_ATTR_TABLE};
 use crate::task::cmdtask::CmdTask;
+use crate::task::exec_context::{CpuSchedPolicy, ExecContext, IoSchedClass};
+use crate::task::kill_context::{KillContext, KillMode};
+use nix::sys::resource::Resource;
+use nix::unistd::Pid;
+use std::collections::VecDeque;
 
 #[derive(Clone, Debug)]
 pub struct ServiceUnit {
     unit_base: BaseUnit,
     service_part: ServicePart,
+    control: ServiceControl,
 }
 
 impl Default for ServiceUnit {
@@ -22,10 +30,51 @@ impl Default for ServiceUnit {
         Self {
             unit_base: BaseUnit::default(),
             service_part: sp,
+            control: ServiceControl::default(),
         }
     }
 }
 
+/// ## 服务当前阶段的控制命令(ExecStartPre=、ExecStop=等)
+///
+/// 同一时刻只运行一个控制命令，其退出被分发后才执行下一个命令或进入下一阶段
+#[derive(Debug, Clone, Default)]
+pub struct ServiceControl {
+    // 当前阶段尚未执行的命令
+    pub pending: VecDeque<CmdTask>,
+    // 正在运行的控制命令，其pid记录在CmdTask中
+    pub running: Option<CmdTask>,
+    // 正在运行的控制命令是否已超时
+    pub timed_out: bool,
+    // 本次运行的结果，服务进入dead阶段时据此决定是否重启
+    pub result: Option<ExitStatus>,
+    // 自动重启的次数，对应NRestarts=
+    pub n_restarts: u32,
+    // daemon-reexec之前运行的主进程，以及控制进程和其在当前阶段命令中的位置，由coldplug接管
+    pub reexec_main: Option<Pid>,
+    pub reexec_control: Option<(Pid, usize)>,
+}
+
+impl ServiceControl {
+    /// ## 终止正在运行的控制命令并丢弃当前阶段剩余的命令
+    ///
+    /// 被终止的控制进程退出时不再被处理
+    pub fn abandon(&mut self) {
+        if let Some(mut cmd) = self.running.take() {
+            cmd.stop();
+        }
+        self.pending.clear();
+        self.timed_out = false;
+    }
+
+    /// 正在运行的控制进程
+    pub fn pid(&self) -> Option<Pid> {
+        self.running
+            .as_ref()
+            .map(|cmd| Pid::from_raw(cmd.pid as i32))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ServiceType {
     Simple,
@@ -121,13 +170,15 @@ pub struct ServicePart {
     timeout_stop_sec: u64,
     //上下文配置相关
     environment: Vec<(String, String)>,
-    nice: i8,
     working_directory: String,
     root_directory: String,
     user: String,
     group: String,
     mount_flags: MountFlag,
-    //LimitCPU / LimitSTACK / LimitNOFILE / LimitNPROC 等,后续支持再添加
+    //进程执行上下文，包括Nice、调度策略、UMask以及LimitCPU / LimitNOFILE 等资源限制
+    exec_context: ExecContext,
+    //停止服务时如何终止其进程，包括KillMode、KillSignal、SendSIGKILL
+    kill_context: KillContext,
 }
 
 impl Unit for ServiceUnit {
@@ -181,29 +232,24 @@ impl Unit for ServiceUnit {
     }
 
     fn init(&mut self) {
+        let id = self.unit_id();
         let part = &mut self.service_part;
-        for cmd in part.exec_reload.iter_mut() {
-            cmd.dir = part.working_directory.to_string();
-            cmd.envs = part.environment.clone();
-        }
-        part.exec_start.dir = part.working_directory.to_string();
-        part.exec_start.envs = part.environment.clone();
-        for cmd in part.exec_start_pos.iter_mut() {
+        let setup = |cmd: &mut CmdTask| {
             cmd.dir = part.working_directory.to_string();
             cmd.envs = part.environment.clone();
-        }
-        for cmd in part.exec_start_pre.iter_mut() {
-            cmd.dir = part.working_directory.to_string();
-            cmd.envs = part.environment.clone();
-        }
-        for cmd in part.exec_stop.iter_mut() {
-            cmd.dir = part.working_directory.to_string();
-            cmd.envs = part.environment.clone();
-        }
-        for cmd in part.exec_stop_post.iter_mut() {
-            cmd.dir = part.working_directory.to_string();
-            cmd.envs = part.environment.clone();
-        }
+            cmd.context = part.exec_context.clone();
+            cmd.unit = id;
+        };
+        part.exec_reload.iter_mut().for_each(setup);
+        setup(&mut part.exec_start);
+        part.exec_start_pos.iter_mut().for_each(setup);
+        part.exec_start_pre.iter_mut().for_each(setup);
+        part.exec_stop.iter_mut().for_each(setup);
+        part.exec_stop_post.iter_mut().for_each(setup);
+    }
+
+    fn add_implicit_dependencies(&mut self) -> Result<(), ParseError> {
+        self.unit_base.mut_unit_part().add_default_dependencies()
     }
 
     fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
@@ -218,6 +264,94 @@ impl Unit for ServiceUnit {
     fn restart(&mut self) -> Result<(), RuntimeError> {
         return ServiceExecutor::restart(self);
     }
+
+    fn reload(&mut self) -> Result<(), RuntimeError> {
+        ServiceExecutor::reload(self)
+    }
+
+    fn control_exit(&mut self, pid: Pid, exit: ProcessExit) {
+        ServiceExecutor::control_exit(self, pid, exit);
+    }
+
+    fn properties(&self) -> Vec<(String, String)> {
+        let mut props = self.unit_base.properties();
+        props.extend(self.service_part.properties());
+
+        // 主进程信息，ExecMain*为最近一次启动的主进程
+        let id = self.unit_id();
+        let main_pid = UnitManager::running_pid(id).map_or(0, |pid| pid.as_raw());
+        let exit = ProcessManager::last_exit(id);
+        let (exec_main_pid, exec_main_code, exec_main_status) = match exit.main {
+            Some((pid, exit)) if main_pid == 0 => (pid.as_raw(), exit.si_code(), exit.status()),
+            _ => (main_pid, 0, 0),
+        };
+        props.push(("MainPID".to_string(), main_pid.to_string()));
+        props.push(("ExecMainPID".to_string(), exec_main_pid.to_string()));
+        props.push(("ExecMainCode".to_string(), exec_main_code.to_string()));
+        props.push(("ExecMainStatus".to_string(), exec_main_status.to_string()));
+        let control_pid = self.control.pid().map_or(0, |pid| pid.as_raw());
+        props.push(("ControlPID".to_string(), control_pid.to_string()));
+        props.push(("NRestarts".to_string(), self.control.n_restarts.to_string()));
+        props
+    }
+
+    fn serialize(&self) -> Vec<(String, String)> {
+        let mut items = self.unit_base.serialize();
+        if let Some(pid) = UnitManager::running_pid(self.unit_id()) {
+            items.push(("main-pid".to_string(), pid.to_string()));
+        }
+        // 控制命令在当前阶段命令中的位置，恢复后据此继续执行剩余的命令
+        if let Some(pid) = self.control.pid() {
+            let phase = self
+                .service_part
+                .phase_commands(*self.unit_base.sub_state());
+            let index = phase.len().saturating_sub(self.control.pending.len() + 1);
+            items.push(("control-pid".to_string(), pid.to_string()));
+            items.push(("control-command".to_string(), index.to_string()));
+        }
+        if let Some(result) = self.control.result {
+            items.push(("result".to_string(), result.name().to_string()));
+        }
+        items.push((
+            "n-restarts".to_string(),
+            self.control.n_restarts.to_string(),
+        ));
+        items
+    }
+
+    fn deserialize_item(&mut self, key: &str, value: &str) {
+        let control = &mut self.control;
+        match key {
+            "main-pid" => control.reexec_main = value.parse().ok().map(Pid::from_raw),
+            "control-pid" => {
+                if let Ok(pid) = value.parse() {
+                    control.reexec_control = Some((Pid::from_raw(pid), 0));
+                }
+            }
+            "control-command" => {
+                if let (Some((_, index)), Ok(value)) =
+                    (control.reexec_control.as_mut(), value.parse())
+                {
+                    *index = value;
+                }
+            }
+            "result" => control.result = ExitStatus::from_name(value),
+            "n-restarts" => control.n_restarts = value.parse().unwrap_or_default(),
+            _ => self.unit_base.deserialize_item(key, value),
+        }
+    }
+
+    fn coldplug(&mut self) {
+        ServiceExecutor::coldplug(self);
+    }
+
+    /// 继承运行中的控制命令及本次运行的结果，正在运行的命令仍按照旧的配置执行完毕
+    fn reload_from(&mut self, old: &dyn Unit) {
+        self.unit_base.inherit_state(old.unit_base());
+        if let Some(old) = old.as_any().downcast_ref::<ServiceUnit>() {
+            self.control = old.control.clone();
+        }
+    }
 }
 
 impl ServiceUnit {
@@ -233,6 +367,14 @@ impl ServiceUnit {
         return &mut self.service_part;
     }
 
+    pub fn control(&self) -> &ServiceControl {
+        &self.control
+    }
+
+    pub fn control_mut(&mut self) -> &mut ServiceControl {
+        &mut self.control
+    }
+
     fn exec(&mut self) -> Result<(), RuntimeError> {
         let _ = ServiceExecutor::exec(self);
         Ok(())
@@ -287,6 +429,46 @@ pub enum ServiceUnitAttr {
     Group,
     //服务的 Mount Namespace 配置，会影响进程上下文中挂载点的信息
     MountFlags,
+    //资源限制(LimitCPU/LimitNOFILE/LimitNPROC/LimitCORE等)，格式为"soft:hard"或单个值
+    Limit(Resource),
+    //CPU调度策略，可选other/batch/idle/fifo/rr
+    CPUSchedulingPolicy,
+    //CPU调度优先级，仅对fifo/rr有效，范围1~99
+    CPUSchedulingPriority,
+    //服务进程可运行的CPU列表，如"0 1 4-7"
+    CPUAffinity,
+    //IO调度类型，可选realtime/best-effort/idle
+    IOSchedulingClass,
+    //IO调度优先级，范围0~7，值越小优先级越高
+    IOSchedulingPriority,
+    //OOM killer分数调整值，范围-1000~1000
+    OOMScoreAdjust,
+    //服务进程的文件创建掩码，默认0022
+    UMask,
+    //标准输入，可选null/tty/tty-force/file:path/socket/data，未设置时继承DragonReach的标准输入
+    StandardInput,
+    //标准输出，可选inherit/null/tty/journal/file:path/append:path/truncate:path/socket
+    StandardOutput,
+    //标准错误，可选值同StandardOutput，默认inherit(与标准输出相同)
+    StandardError,
+    //StandardInput=data时的输入文本，每次设置追加一行
+    StandardInputText,
+    //StandardInput=data时的输入数据，使用base64编码
+    StandardInputData,
+    //StandardInput/StandardOutput等设置为tty时使用的终端，默认/dev/console
+    TTYPath,
+    //服务启动前及退出后重置终端
+    TTYReset,
+    //服务启动前及退出后挂断打开该终端的所有进程
+    TTYVHangup,
+    //服务启动前及退出后释放TTYPath对应的虚拟终端
+    TTYVTDisallocate,
+    //停止服务时向哪些进程发送信号，可选control-group/mixed/process/none
+    KillMode,
+    //停止服务时首先发送的信号，默认SIGTERM
+    KillSignal,
+    //TimeoutStopSec后仍有进程未退出时是否发送SIGKILL，默认yes
+    SendSIGKILL,
 }
 
 #[allow(dead_code)]
@@ -357,7 +539,103 @@ impl ServicePart {
                     .extend(UnitParseUtil::parse_environment_file(val)?);
             }
             ServiceUnitAttr::Nice => {
-                self.nice = UnitParseUtil::parse_nice(val)?;
+                self.exec_context.set_nice(UnitParseUtil::parse_nice(val)?);
+            }
+            ServiceUnitAttr::CPUSchedulingPolicy => match val {
+                "other" => self
+                    .exec_context
+                    .set_cpu_sched_policy(CpuSchedPolicy::Other),
+                "batch" => self
+                    .exec_context
+                    .set_cpu_sched_policy(CpuSchedPolicy::Batch),
+                "idle" => self.exec_context.set_cpu_sched_policy(CpuSchedPolicy::Idle),
+                "fifo" => self.exec_context.set_cpu_sched_policy(CpuSchedPolicy::Fifo),
+                "rr" => self
+                    .exec_context
+                    .set_cpu_sched_policy(CpuSchedPolicy::RoundRobin),
+                _ => {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+            },
+            ServiceUnitAttr::CPUSchedulingPriority => {
+                self.exec_context
+                    .set_cpu_sched_priority(UnitParseUtil::parse_cpu_sched_priority(val)?);
+            }
+            ServiceUnitAttr::CPUAffinity => {
+                self.exec_context
+                    .extend_cpu_affinity(&UnitParseUtil::parse_cpu_set(val)?);
+            }
+            ServiceUnitAttr::IOSchedulingClass => match val {
+                "realtime" | "1" => self.exec_context.set_io_sched_class(IoSchedClass::Realtime),
+                "best-effort" | "2" => self
+                    .exec_context
+                    .set_io_sched_class(IoSchedClass::BestEffort),
+                "idle" | "3" => self.exec_context.set_io_sched_class(IoSchedClass::Idle),
+                _ => {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+            },
+            ServiceUnitAttr::IOSchedulingPriority => {
+                self.exec_context
+                    .set_io_sched_priority(UnitParseUtil::parse_io_sched_priority(val)?);
+            }
+            ServiceUnitAttr::OOMScoreAdjust => {
+                self.exec_context
+                    .set_oom_score_adjust(UnitParseUtil::parse_oom_score_adjust(val)?);
+            }
+            ServiceUnitAttr::UMask => {
+                self.exec_context.set_umask(UnitParseUtil::parse_mode(val)?);
+            }
+            ServiceUnitAttr::StandardInput => {
+                self.exec_context.stdio_mut().input = UnitParseUtil::parse_std_input(val)?;
+            }
+            ServiceUnitAttr::StandardOutput => {
+                self.exec_context.stdio_mut().output = Some(UnitParseUtil::parse_std_output(val)?);
+            }
+            ServiceUnitAttr::StandardError => {
+                self.exec_context.stdio_mut().error = UnitParseUtil::parse_std_output(val)?;
+            }
+            ServiceUnitAttr::StandardInputText => {
+                let data = &mut self.exec_context.stdio_mut().input_data;
+                data.extend_from_slice(val.as_bytes());
+                data.push(b'\n');
+            }
+            ServiceUnitAttr::StandardInputData => {
+                let decoded = UnitParseUtil::parse_base64(val)?;
+                self.exec_context.stdio_mut().input_data.extend(decoded);
+            }
+            ServiceUnitAttr::TTYPath => {
+                if !val.starts_with('/') {
+                    return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                }
+                self.exec_context.stdio_mut().tty_path = Some(String::from(val));
+            }
+            ServiceUnitAttr::TTYReset => {
+                self.exec_context.stdio_mut().tty_reset = UnitParseUtil::parse_boolean(val)?;
+            }
+            ServiceUnitAttr::TTYVHangup => {
+                self.exec_context.stdio_mut().tty_vhangup = UnitParseUtil::parse_boolean(val)?;
+            }
+            ServiceUnitAttr::KillMode => {
+                self.kill_context.kill_mode = match val {
+                    "control-group" => KillMode::ControlGroup,
+                    "mixed" => KillMode::Mixed,
+                    "process" => KillMode::Process,
+                    "none" => KillMode::None,
+                    _ => {
+                        return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
+                    }
+                };
+            }
+            ServiceUnitAttr::KillSignal => {
+                self.kill_context.kill_signal = UnitParseUtil::parse_signal(val)?;
+            }
+            ServiceUnitAttr::SendSIGKILL => {
+                self.kill_context.send_sigkill = UnitParseUtil::parse_boolean(val)?;
+            }
+            ServiceUnitAttr::TTYVTDisallocate => {
+                self.exec_context.stdio_mut().tty_vt_disallocate =
+                    UnitParseUtil::parse_boolean(val)?;
             }
             ServiceUnitAttr::WorkingDirectory => {
                 if !UnitParseUtil::is_dir(val) {
@@ -381,6 +659,10 @@ impl ServicePart {
                     return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
                 }
             },
+            ServiceUnitAttr::Limit(resource) => {
+                let limit = UnitParseUtil::parse_rlimit(val, *resource)?;
+                self.exec_context.set_limit(*resource, limit);
+            }
             _ => {
                 return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
             }
@@ -417,6 +699,18 @@ impl ServicePart {
         &self.exec_stop
     }
 
+    /// 各阶段执行的控制命令，不执行控制命令的阶段为空
+    pub fn phase_commands(&self, phase: UnitSubState) -> &[CmdTask] {
+        match phase {
+            UnitSubState::StartPre => &self.exec_start_pre,
+            UnitSubState::StartPost => &self.exec_start_pos,
+            UnitSubState::Reload => &self.exec_reload,
+            UnitSubState::Stop => &self.exec_stop,
+            UnitSubState::StopPost => &self.exec_stop_post,
+            _ => &[],
+        }
+    }
+
     pub fn exec_stop_post(&mut self) -> &mut Vec<CmdTask> {
         &mut self.exec_stop_post
     }
@@ -463,7 +757,7 @@ impl ServicePart {
     }
 
     pub fn nice(&self) -> i8 {
-        self.nice
+        self.exec_context.nice().unwrap_or(0)
     }
 
     pub fn working_directory(&self) -> &str {
@@ -485,4 +779,109 @@ impl ServicePart {
     pub fn mount_flags(&self) -> &MountFlag {
         &self.mount_flags
     }
+
+    pub fn exec_context(&self) -> &ExecContext {
+        &self.exec_context
+    }
+
+    pub fn kill_context(&self) -> &KillContext {
+        &self.kill_context
+    }
+
+    /// ## Service段的属性，用于show命令输出
+    pub fn properties(&self) -> Vec<(String, String)> {
+        let service_type = match self.service_type {
+            ServiceType::Simple => "simple",
+            ServiceType::Forking => "forking",
+            ServiceType::OneShot => "oneshot",
+            ServiceType::Dbus => "dbus",
+            ServiceType::Notify => "notify",
+            ServiceType::Idle => "idle",
+        };
+        let exec_start = if self.exec_start.path.is_empty() {
+            String::new()
+        } else {
+            format!("{} {}", self.exec_start.path, self.exec_start.cmd.join(" "))
+        };
+        let mut props = vec![
+            ("Type".to_string(), service_type.to_string()),
+            ("ExecStart".to_string(), exec_start.trim_end().to_string()),
+            (
+                "WorkingDirectory".to_string(),
+                self.working_directory.clone(),
+            ),
+            ("RootDirectory".to_string(), self.root_directory.clone()),
+            ("User".to_string(), self.user.clone()),
+            ("Group".to_string(), self.group.clone()),
+        ];
+        props.extend(self.exec_context.properties());
+        props.extend(self.kill_context.properties());
+        props
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::UnitState;
+
+    fn cmd(pid: u32) -> CmdTask {
+        CmdTask {
+            path: "/bin/true".to_string(),
+            pid,
+            ..Default::default()
+        }
+    }
+
+    fn round_trip(service: &ServiceUnit) -> ServiceUnit {
+        let mut restored = ServiceUnit::default();
+        for (key, value) in service.serialize() {
+            restored.deserialize_item(&key, &value);
+        }
+        restored
+    }
+
+    #[test]
+    fn service_round_trip_main_and_control() {
+        // 使用不会被分配的id，避免与其他测试共用运行表中的项
+        let id = usize::MAX - 42;
+        let mut service = ServiceUnit::default();
+        service.unit_base.unit_id = id;
+        service.unit_base.state = UnitState::Deactivating;
+        service.unit_base.sub_state = UnitSubState::Stop;
+        service.service_part.exec_stop = vec![cmd(0), cmd(0), cmd(0)];
+        // 第二个ExecStop=正在运行，第三个尚未执行
+        service.control.running = Some(cmd(4321));
+        service.control.pending = VecDeque::from(vec![cmd(0)]);
+        service.control.result = Some(ExitStatus::Watchdog);
+        service.control.n_restarts = 3;
+
+        UnitManager::push_running(id, Pid::from_raw(1234));
+        let restored = round_trip(&service);
+        UnitManager::remove_running(id);
+
+        assert_eq!(*restored.unit_base.state(), UnitState::Deactivating);
+        assert_eq!(*restored.unit_base.sub_state(), UnitSubState::Stop);
+        assert_eq!(restored.control.reexec_main, Some(Pid::from_raw(1234)));
+        assert_eq!(
+            restored.control.reexec_control,
+            Some((Pid::from_raw(4321), 1))
+        );
+        assert_eq!(
+            restored.control.result.map(|r| r.name()),
+            Some(ExitStatus::Watchdog.name())
+        );
+        assert_eq!(restored.control.n_restarts, 3);
+    }
+
+    #[test]
+    fn service_round_trip_without_processes() {
+        let mut service = ServiceUnit::default();
+        service.unit_base.unit_id = usize::MAX - 43;
+        let restored = round_trip(&service);
+        assert_eq!(restored.control.reexec_main, None);
+        assert_eq!(restored.control.reexec_control, None);
+        assert!(restored.control.result.is_none());
+        assert_eq!(restored.control.n_restarts, 0);
+    }
 }
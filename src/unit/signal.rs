@@ -1,15 +1,21 @@
-use nix::sys::signal::{self, SigHandler, Signal};
-use std::sync::atomic::{AtomicBool, Ordering};
+use nix::sys::signal::{SigSet, Signal};
 
-pub static SIGCHILD_SIGNAL_RECEIVED: AtomicBool = AtomicBool::new(false);
-
-extern "C" fn handle_sigchld(_: libc::c_int) {
-    SIGCHILD_SIGNAL_RECEIVED.store(true, Ordering::SeqCst);
+/// DragonReach通过signalfd处理的信号
+pub fn manager_sigset() -> SigSet {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGCHLD);
+    mask.add(Signal::SIGTERM);
+    mask.add(Signal::SIGINT);
+    mask.add(Signal::SIGHUP);
+    mask
 }
 
+/// ## 屏蔽由signalfd处理的信号
+///
+/// 需在创建任何子进程之前调用，避免错过子进程的SIGCHLD。
+/// 子进程exec之前信号屏蔽字会被重置，不会继承该设置
 pub fn init_signal_handler() {
-    unsafe {
-        signal::signal(Signal::SIGCHLD, SigHandler::Handler(handle_sigchld))
-            .expect("Error setting SIGUSR1 handler");
-    }
+    manager_sigset()
+        .thread_block()
+        .expect("Error blocking manager signals");
 }
@@ -1,12 +1,14 @@
 use super::{BaseUnit, Unit};
 use crate::error::parse_error::{ParseError, ParseErrorType};
 use crate::error::runtime_error::{RuntimeError, RuntimeErrorType};
-use crate::executor::Executor;
+use crate::manager::job_manager::{JobManager, JobMode, JobType};
 use crate::manager::timer_manager::TimerManager;
 use crate::manager::UnitManager;
 use crate::parse::parse_timer::TimerParser;
+use crate::parse::parse_util::UnitParseUtil;
 use crate::parse::{Segment, TIMER_UNIT_ATTR_TABLE};
 use crate::time::calandar::CalendarStandard;
+use crate::time::{instant_to_monotonic_usec, monotonic_usec_to_instant};
 use crate::unit::UnitState;
 use humantime::parse_duration;
 use std::fmt::Debug;
@@ -91,6 +93,15 @@ impl Unit for TimerUnit {
         // 将单元状态设置为激活
         self.unit_base.state = UnitState::Active;
     }
+    /// 添加默认依赖，并在所激活的单元之前启动
+    fn add_implicit_dependencies(&mut self) -> Result<(), ParseError> {
+        let part = self.unit_base.mut_unit_part();
+        part.add_default_dependencies()?;
+        if self.timer_part.unit != 0 {
+            part.push_before_unit(self.timer_part.unit);
+        }
+        Ok(())
+    }
     /// 设置单元的名称
     fn set_unit_name(&mut self, name: String) {
         self.unit_base_mut().unit_name = name;
@@ -166,6 +177,86 @@ impl Unit for TimerUnit {
     fn exit(&mut self) {
         UnitManager::try_kill_running(self.unit_id());
     }
+
+    fn serialize(&self) -> Vec<(String, String)> {
+        // 计时的状态记录在计时器管理中的TimerUnit上
+        let timer = TimerManager::get_timer_unit(self.unit_id());
+        let timer = timer.as_ref().map(|timer| timer.lock().unwrap());
+        let part = timer
+            .as_ref()
+            .map_or(&self.timer_part, |timer| &timer.timer_part);
+        let mut items = self.unit_base.serialize();
+        items.push((
+            "last-trigger".to_string(),
+            instant_to_monotonic_usec(part.last_trigger).to_string(),
+        ));
+        let values: Vec<String> = part.value.iter().map(|val| val.serialize()).collect();
+        items.push(("values".to_string(), values.join(";")));
+        if let Some(next_elapse) = part.next_elapse_monotonic_or_boottime {
+            items.push((
+                "next-elapse".to_string(),
+                instant_to_monotonic_usec(next_elapse).to_string(),
+            ));
+        }
+        items
+    }
+
+    fn deserialize_item(&mut self, key: &str, value: &str) {
+        let part = &mut self.timer_part;
+        match key {
+            "last-trigger" => {
+                if let Ok(usec) = value.parse() {
+                    part.last_trigger = monotonic_usec_to_instant(usec);
+                }
+            }
+            // 序列化时values在next-elapse之前，没有next-elapse表示下次触发时间无限大
+            "values" => {
+                part.value = value.split(';').filter_map(TimerVal::deserialize).collect();
+                part.next_elapse_monotonic_or_boottime = None;
+            }
+            "next-elapse" => {
+                if let Ok(usec) = value.parse() {
+                    part.next_elapse_monotonic_or_boottime = Some(monotonic_usec_to_instant(usec));
+                }
+            }
+            _ => self.unit_base.deserialize_item(key, value),
+        }
+    }
+
+    /// 继承重新解析之前的计时状态，并以此替换重新解析时加入计时器管理的计时器
+    ///
+    /// 已消耗的计时值不会再次触发，已失效的计时器不再计时
+    fn reload_from(&mut self, old: &dyn Unit) {
+        self.unit_base.inherit_state(old.unit_base());
+        if let Some(old) = old.as_any().downcast_ref::<TimerUnit>() {
+            let (part, old) = (&mut self.timer_part, &old.timer_part);
+            part.last_trigger = old.last_trigger;
+            part.value = old.value.clone();
+            part.next_elapse_monotonic_or_boottime = old.next_elapse_monotonic_or_boottime;
+        }
+        self.coldplug();
+    }
+
+    /// 同时替换所激活的单元，计时中的TimerUnit随之改为监视新的单元
+    fn replace_dependency(&mut self, from: usize, to: usize) {
+        self.unit_base.replace_dependency(from, to);
+        if self.timer_part.unit != from {
+            return;
+        }
+        self.timer_part.unit = to;
+        if let Some(timer) = TimerManager::get_timer_unit(self.unit_id()) {
+            timer.lock().unwrap().timer_part.unit = to;
+            TimerManager::push_timer_unit(timer);
+        }
+    }
+
+    /// 以恢复的状态替换解析时加入计时器管理的计时器
+    fn coldplug(&mut self) {
+        TimerManager::remove_timer_unit(self.unit_id());
+        if self.unit_base.state != UnitState::Inactive {
+            self._init();
+        }
+    }
 }
 
 impl TimerUnit {
@@ -188,7 +279,7 @@ impl TimerUnit {
         }
 
         //执行相应的unit单元
-        if let Ok(_) = Executor::exec(part.unit) {
+        if JobManager::add_job(part.unit, JobType::Start, JobMode::Replace).is_ok() {
             self.unit_base.state = UnitState::Active;
             part.last_trigger = Instant::now();
             return Ok(());
@@ -232,6 +323,15 @@ impl TimerUnit {
         return false;
     }
 
+    /// ## 获取下次触发的时间，不会触发时返回None
+    pub fn next_elapse(&self) -> Option<Instant> {
+        let part = &self.timer_part;
+        if self.unit_base.state == UnitState::Inactive || UnitManager::is_running_unit(&part.unit) {
+            return None;
+        }
+        part.next_elapse_monotonic_or_boottime
+    }
+
     pub fn unit_base(&self) -> &BaseUnit {
         &self.unit_base
     }
@@ -494,7 +594,7 @@ impl TimerPart {
                     }
                 }
             }
-            TimerUnitAttr::Unit => self.unit = UnitManager::get_id_with_path(val).unwrap(),
+            TimerUnitAttr::Unit => self.unit = UnitParseUtil::load_unit(val)?,
             _ => {
                 return Err(ParseError::new(ParseErrorType::EINVAL, String::new(), 0));
             }
@@ -554,4 +654,157 @@ impl TimerVal {
             next_elapse,
         }
     }
+    /// ## 序列化为"属性,是否禁用,时长,下次触发时间"的形式，时间为CLOCK_MONOTONIC的微秒数
+    fn serialize(&self) -> String {
+        let attr = match self.attr {
+            TimerUnitAttr::OnActiveSec => "OnActiveSec",
+            TimerUnitAttr::OnBootSec => "OnBootSec",
+            TimerUnitAttr::OnStartUpSec => "OnStartupSec",
+            TimerUnitAttr::OnUnitActiveSec => "OnUnitActiveSec",
+            TimerUnitAttr::OnUnitInactiveSec => "OnUnitInactiveSec",
+            _ => "OnCalendar",
+        };
+        let next_elapse = self.next_elapse.map_or("-".to_string(), |t| {
+            instant_to_monotonic_usec(t).to_string()
+        });
+        format!(
+            "{},{},{},{}",
+            attr,
+            self.disabled as u8,
+            self.val.as_micros(),
+            next_elapse
+        )
+    }
+
+    fn deserialize(s: &str) -> Option<TimerVal> {
+        let fields: Vec<&str> = s.split(',').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        let attr = *TIMER_UNIT_ATTR_TABLE.get(fields[0])?;
+        let next_elapse = match fields[3] {
+            "-" => None,
+            usec => Some(monotonic_usec_to_instant(usec.parse().ok()?)),
+        };
+        Some(TimerVal::new(
+            attr,
+            fields[1] == "1",
+            Duration::from_micros(fields[2].parse().ok()?),
+            Vec::new(),
+            next_elapse,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 经过CLOCK_MONOTONIC换算的时间点允许有微秒级的误差
+    fn close(a: Instant, b: Instant) -> bool {
+        let diff = if a > b { a - b } else { b - a };
+        diff < Duration::from_millis(1)
+    }
+
+    #[test]
+    fn timer_val_round_trip() {
+        let next = Instant::now() + Duration::from_secs(30);
+        let val = TimerVal::new(
+            TimerUnitAttr::OnStartUpSec,
+            true,
+            Duration::from_micros(1_500_000),
+            Vec::new(),
+            Some(next),
+        );
+        let restored = TimerVal::deserialize(&val.serialize()).unwrap();
+        assert!(matches!(restored.attr, TimerUnitAttr::OnStartUpSec));
+        assert!(restored.disabled);
+        assert_eq!(restored.val, Duration::from_micros(1_500_000));
+        assert!(close(restored.next_elapse.unwrap(), next));
+    }
+
+    #[test]
+    fn timer_val_round_trip_without_next_elapse() {
+        let val = TimerVal::new(
+            TimerUnitAttr::OnUnitInactiveSec,
+            false,
+            Duration::from_secs(5),
+            Vec::new(),
+            None,
+        );
+        let s = val.serialize();
+        assert_eq!(s, "OnUnitInactiveSec,0,5000000,-");
+        let restored = TimerVal::deserialize(&s).unwrap();
+        assert!(matches!(restored.attr, TimerUnitAttr::OnUnitInactiveSec));
+        assert!(!restored.disabled);
+        assert_eq!(restored.val, Duration::from_secs(5));
+        assert!(restored.next_elapse.is_none());
+    }
+
+    fn timer(id: usize, state: UnitState, attrs: &[TimerUnitAttr]) -> TimerUnit {
+        let mut timer = TimerUnit::default();
+        timer.unit_base.unit_id = id;
+        timer.unit_base.state = state;
+        for attr in attrs {
+            let now = Instant::now();
+            timer.timer_part.value.push(TimerVal::new(
+                *attr,
+                false,
+                Duration::from_secs(60),
+                Vec::new(),
+                Some(now + Duration::from_secs(60)),
+            ));
+        }
+        timer.timer_part.update_next_trigger();
+        timer
+    }
+
+    #[test]
+    fn reload_keeps_timer_schedule() {
+        // 使用不会被分配的id，避免与其他测试共用计时器管理中的项
+        let id = usize::MAX - 42;
+        // OnActiveSec=已经触发过，只剩OnUnitActiveSec=
+        let mut old = timer(id, UnitState::Active, &[TimerUnitAttr::OnUnitActiveSec]);
+        old.timer_part.last_trigger = Instant::now() - Duration::from_secs(30);
+        let next = Instant::now() + Duration::from_secs(10);
+        old.timer_part.next_elapse_monotonic_or_boottime = Some(next);
+
+        let mut new = timer(
+            id,
+            UnitState::Active,
+            &[TimerUnitAttr::OnActiveSec, TimerUnitAttr::OnUnitActiveSec],
+        );
+        new.reload_from(&old);
+
+        let live = TimerManager::get_timer_unit(id).unwrap();
+        TimerManager::remove_timer_unit(id);
+        for part in [&new.timer_part, &live.lock().unwrap().timer_part] {
+            assert_eq!(part.value.len(), 1);
+            assert!(matches!(part.value[0].attr, TimerUnitAttr::OnUnitActiveSec));
+            assert_eq!(part.last_trigger, old.timer_part.last_trigger);
+            assert_eq!(part.next_elapse_monotonic_or_boottime, Some(next));
+        }
+    }
+
+    #[test]
+    fn reload_does_not_restart_inactive_timer() {
+        let id = usize::MAX - 43;
+        let old = timer(id, UnitState::Inactive, &[]);
+        let mut new = timer(id, UnitState::Active, &[TimerUnitAttr::OnActiveSec]);
+        new._init();
+        new.reload_from(&old);
+
+        assert!(TimerManager::get_timer_unit(id).is_none());
+        assert_eq!(new.unit_base.state, UnitState::Inactive);
+        assert!(new.timer_part.value.is_empty());
+    }
+
+    #[test]
+    fn timer_val_deserialize_rejects_malformed() {
+        assert!(TimerVal::deserialize("").is_none());
+        assert!(TimerVal::deserialize("OnBootSec,0,5").is_none());
+        assert!(TimerVal::deserialize("NoSuchSec,0,5,-").is_none());
+        assert!(TimerVal::deserialize("OnBootSec,0,abc,-").is_none());
+        assert!(TimerVal::deserialize("OnBootSec,0,5,abc").is_none());
+    }
 }